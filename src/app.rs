@@ -5,21 +5,90 @@ use crate::*;
 
 use websocket::connect;
 
-use iced::Subscription;
+use iced::{event, window, Event, Subscription};
 
 use message::Message;
 
-use iced::Task;
+use iced::{task, Task};
 
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use profile::Profile;
+use profile::{Profile, ProfileDraft};
+
+use paste::ShareError;
+
+use preview::Preview;
+
+use qr::ShareQr;
 
 use error::Error;
 
-use game_settings::GameSettings;
+use game_settings::{GameSettings, SettingsFiles, MAX_FILE_SIZE};
+
+use config::{Applied, Candidate, Config, Install};
+
+use lock::LockGuard;
+
+/// How often the live settings are compared with the profile used last.
+const DRIFT_INTERVAL: Duration = Duration::from_secs(10);
 
-use config::Config;
+/// Lines from the end of the log included in the copied diagnostics.
+const DIAGNOSTICS_LOG_LINES: usize = 100;
+
+/// What a paste service request is for, only one of each kind runs at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum RequestKind {
+    /// Generating a link for the named profile.
+    Upload(String),
+    Download,
+}
+
+/// Paste service request that's running, dropping it cancels the request.
+pub(crate) struct Request {
+    /// Failed try, the wait before the next one and why it failed, once retrying.
+    retry: Option<(u32, Duration, ShareError)>,
+    _handle: task::Handle,
+}
+
+impl Request {
+    fn status(&self, kind: &RequestKind) -> String {
+        let label = match kind {
+            RequestKind::Upload(profile_name) => format!("Generating link for \"{profile_name}\""),
+            RequestKind::Download => "Downloading profile".to_string(),
+        };
+        match &self.retry {
+            Some((attempt, delay, error)) => format!(
+                "{label} - {error}, try {} of {} in {}s",
+                attempt + 1,
+                paste::MAX_ATTEMPTS,
+                delay.as_secs()
+            ),
+            None => format!("{label}..."),
+        }
+    }
+}
+
+/// Panel to choose which settings are locked from the live ones.
+pub(crate) struct KeyPicker {
+    filter: String,
+    live: BTreeMap<String, String>,
+}
+
+impl KeyPicker {
+    fn new(cfg: &GameSettings) -> Result<Self, Error> {
+        let dir = cfg.game.parent().ok_or(Error::WrongPath)?;
+        Ok(Self {
+            filter: String::new(),
+            live: SettingsFiles::from_dir(dir)?.values(),
+        })
+    }
+}
 
 #[derive(Default)]
 pub(crate) struct App {
@@ -32,8 +101,20 @@ pub(crate) struct App {
     champion_id: Option<u32>,
     connected: bool,
     retry_in: Option<u32>,
-    client: Arc<Mutex<reqwest::Client>>,
+    client: reqwest::Client,
+    requests: HashMap<RequestKind, Request>,
     link: String,
+    dropped_files: Vec<PathBuf>,
+    pending: Vec<ProfileDraft>,
+    preview: Option<Preview>,
+    qr: Option<ShareQr>,
+    /// Install whose client the websocket is connected to, auto-swap applies profiles to it.
+    connected_install: Option<Install>,
+    /// Locked settings, only kept while locking is enforced.
+    lock_guard: Option<LockGuard>,
+    key_picker: Option<KeyPicker>,
+    /// Profile last used on the selected install, when the live settings changed since.
+    drift: Option<String>,
 }
 
 impl App {
@@ -50,22 +131,309 @@ impl App {
                 self.error = Some(e);
             }
         }
-        self.error
+        self.key_picker = None;
+        self.resume_lock();
+        self.error.clone()
+    }
+
+    /// Locks the current live settings if locking is enforced, called whenever the app itself
+    /// changes them so they aren't taken back.
+    fn refresh_lock(&mut self) {
+        self.lock_guard = match &self.cfg {
+            Some(cfg) if self.readonly && self.config.enforce_lock() => match LockGuard::new(cfg) {
+                Ok(guard) => Some(guard),
+                Err(e) => {
+                    log::warn!(error:% = e; "couldn't lock the live settings");
+                    // keeps a more important error from startup
+                    self.error.get_or_insert(e);
+                    None
+                }
+            },
+            Some(_) if !self.config.locked_keys().is_empty() => {
+                Some(LockGuard::keys(self.config.locked_keys().clone()))
+            }
+            _ => None,
+        };
+        self.save_lock();
+    }
+
+    /// Picks the lock saved for the selected install back up on startup and when switching
+    /// installs, so whatever the client wrote in the meantime is put back. The files are made
+    /// readonly again on the next check.
+    fn resume_lock(&mut self) {
+        let saved = self
+            .config
+            .selected_install()
+            .and_then(|install| install.locked.clone());
+        match saved {
+            Some(files) if self.cfg.is_some() && self.config.enforce_lock() => {
+                self.readonly = true;
+                self.lock_guard = Some(LockGuard::saved(files));
+            }
+            _ => self.refresh_lock(),
+        }
+    }
+
+    /// Saves what's locked on the selected install so it's still locked after a restart.
+    fn save_lock(&mut self) {
+        let (Some(_), Some(path)) = (&self.cfg, self.config.path().clone()) else {
+            return;
+        };
+        let locked = self.lock_guard.as_ref().and_then(LockGuard::files).cloned();
+        if let Err(e) = self.config.set_locked(&path, locked) {
+            log::warn!(error:% = e; "couldn't save the locked settings");
+            self.error.get_or_insert(e);
+        }
     }
 
     fn get_profile_from_name(&mut self, name: &String) -> Option<&mut Profile> {
         self.profiles.iter_mut().find(|p| p.name().eq(name))
     }
+
+    /// Imports a file or folder dropped onto the window.
+    ///
+    /// Loose `game.cfg` and `PersistedSettings.json` arrive as separate drops, so they are kept
+    /// until both are there.
+    fn import_dropped(&mut self, path: PathBuf) -> Task<Message> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        let loose = !path.is_dir() && matches!(extension.as_deref(), Some("cfg") | Some("json"));
+        if loose {
+            // a newer file of the same kind replaces the older one so two profiles never mix
+            self.dropped_files.retain(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.to_ascii_lowercase())
+                    != extension
+            });
+        } else {
+            self.dropped_files.clear();
+        }
+
+        let imported = match extension.as_deref() {
+            _ if path.is_dir() => ProfileDraft::from_dir(&path).map(|d| vec![d]),
+            Some("zip") => ProfileDraft::from_zip(&path),
+            Some("cfg") | Some("json") => {
+                self.dropped_files.push(path);
+                match ProfileDraft::from_files(&self.dropped_files) {
+                    Err(Error::MissingSettingsFile) => return Task::none(),
+                    result => {
+                        self.dropped_files.clear();
+                        result.map(|d| vec![d])
+                    }
+                }
+            }
+            _ => {
+                return match Self::read_dropped_link(&path) {
+                    Some(link) => Task::done(Message::FetchLink(link)),
+                    None => {
+                        self.error = Some(Error::UnsupportedDrop);
+                        Task::none()
+                    }
+                };
+            }
+        };
+
+        self.queue_imports(imported);
+        Task::none()
+    }
+
+    /// Queues read profiles for the import preview, nothing is saved until they are accepted.
+    fn queue_imports(&mut self, drafts: Result<Vec<ProfileDraft>, Error>) {
+        match drafts {
+            Ok(mut drafts) => {
+                self.pending.append(&mut drafts);
+                self.refresh_preview();
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn refresh_preview(&mut self) {
+        let live = self
+            .cfg
+            .as_ref()
+            .and_then(|c| SettingsFiles::from_paths(&c.game, &c.settings).ok());
+        self.preview = self
+            .pending
+            .first()
+            .map(|draft| Preview::new(draft.name(), draft.files(), live.as_ref()));
+    }
+
+    /// App and install state with the end of the log, pasted into bug reports.
+    fn diagnostics(&self) -> String {
+        let mut lines = vec![
+            format!("League Config Manager {}", env!("CARGO_PKG_VERSION")),
+            format!("OS: {} {}", std::env::consts::OS, std::env::consts::ARCH),
+            format!(
+                "Data folder: {}{}",
+                Config::get_config_dir().display(),
+                if Config::is_portable() {
+                    " (portable)"
+                } else {
+                    ""
+                }
+            ),
+            format!("Share service: {}", self.config.share()),
+            format!(
+                "Readonly: {}, enforce lock: {}, locked settings: {}",
+                self.readonly,
+                self.config.enforce_lock(),
+                self.config.locked_keys().len()
+            ),
+            format!("Profiles: {}", self.profiles.len()),
+            format!(
+                "Client: {}",
+                match (&self.connected_install, self.connected) {
+                    (Some(install), _) => format!("connected to {}", install.name),
+                    (None, true) => "connected".to_string(),
+                    (None, false) => "not connected".to_string(),
+                }
+            ),
+        ];
+        for install in self.config.installs() {
+            lines.push(format!(
+                "Install {}: {}{}",
+                install.name,
+                install.path.display(),
+                match &install.applied {
+                    Some(applied) => format!(", using \"{}\"", applied.profile),
+                    None => String::new(),
+                }
+            ));
+        }
+        lines.push(String::new());
+        lines.push(logging::tail(DIAGNOSTICS_LOG_LINES));
+        lines.join("\n")
+    }
+
+    fn read_dropped_link(path: &Path) -> Option<String> {
+        if fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+            return None;
+        }
+        let content = fs::read_to_string(path).ok()?;
+        content
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("http"))
+            .map(str::to_string)
+    }
+}
+
+impl App {
+    /// Settings auto-swap writes to, the connected install's when it's known.
+    fn swap_target(&self) -> Option<(PathBuf, GameSettings)> {
+        match &self.connected_install {
+            Some(install) => Some((install.path.clone(), install.game_settings().ok()?)),
+            None => Some((self.config.path().clone()?, self.cfg.clone()?)),
+        }
+    }
+
+    /// Uses `profile` on the selected install.
+    fn use_profile(&mut self, profile: &Profile) -> Result<(), Error> {
+        let (Some(cfg), Some(path)) = (self.cfg.clone(), self.config.path().clone()) else {
+            return Err(Error::MissingPath);
+        };
+        profile.copy_files(&cfg)?;
+        self.record_applied(&path, cfg, profile);
+        self.refresh_lock();
+        Ok(())
+    }
+
+    /// Remembers `profile` was just copied to the install at `path`.
+    fn record_applied(&mut self, path: &Path, cfg: GameSettings, profile: &Profile) {
+        log::info!(profile:% = profile.name(), install:% = path.display(); "applied profile");
+        let applied = Applied {
+            profile: profile.name().clone(),
+            hash: cfg.files_hash(),
+        };
+        if let Err(e) = self.config.set_applied(path, Some(applied)) {
+            self.error = Some(e);
+        }
+        // a saved lock would take the profile back once the install is selected again
+        let locked = self
+            .config
+            .installs()
+            .iter()
+            .any(|i| i.path == path && i.locked.is_some());
+        if let (true, Ok(files)) = (locked, SettingsFiles::from_paths(&cfg.game, &cfg.settings)) {
+            if let Err(e) = self.config.set_locked(path, Some(files)) {
+                self.error = Some(e);
+            }
+        }
+        self.check_drift();
+    }
+
+    /// Compares the live settings with the profile last used on the selected install.
+    fn check_drift(&mut self) {
+        self.drift = None;
+        let (Some(cfg), Some(install)) = (&self.cfg, self.config.selected_install()) else {
+            return;
+        };
+        let Some(applied) = install.applied.clone() else {
+            return;
+        };
+        let hash = cfg.files_hash();
+        if hash == applied.hash {
+            return;
+        }
+        let Some(profile) = self.profiles.iter().find(|p| *p.name() == applied.profile) else {
+            return;
+        };
+        if profile.matches(cfg, self.config.locked_keys()) {
+            // rewritten without changing a setting, e.g. locked settings put back
+            let path = install.path.clone();
+            if let Err(e) = self
+                .config
+                .set_applied(&path, Some(Applied { hash, ..applied }))
+            {
+                self.error = Some(e);
+            }
+        } else {
+            self.drift = Some(applied.profile);
+        }
+    }
 }
 
 impl App {
-    pub(crate) fn new() -> (App, iced::Task<Message>) {
-        let conf = Config::new();
+    /// Tracks `task` as the running request of its kind, unless one is running already.
+    fn start_request(&mut self, kind: RequestKind, task: Task<Message>) -> Task<Message> {
+        if self.requests.contains_key(&kind) {
+            return Task::none();
+        }
+        let (task, handle) = task
+            .chain(Task::done(Message::RequestFinished(kind.clone())))
+            .abortable();
+        self.requests.insert(
+            kind,
+            Request {
+                retry: None,
+                _handle: handle.abort_on_drop(),
+            },
+        );
+        task
+    }
+
+    fn is_running(&self, kind: &RequestKind) -> bool {
+        self.requests.contains_key(kind)
+    }
+}
+
+impl App {
+    /// `link` is a profile link or share code the app was opened with, it's imported right away.
+    pub(crate) fn new(link: Option<String>) -> (App, iced::Task<Message>) {
+        let (conf, config_error) = Config::new();
+        for install in conf.installs() {
+            GameSettings::recover(&Config::game_settings_path(&install.path));
+        }
         let mut cfg = None;
         let mut readonly = false;
         let mut err = None;
-        let profiles = Profile::profiles();
-        let client = Arc::new(Mutex::new(reqwest::Client::new()));
+        let (profiles, profile_errors) = Profile::load_all();
+        let client = paste::client();
 
         match GameSettings::from_config(&conf) {
             Ok(c) => {
@@ -74,25 +442,63 @@ impl App {
             }
             Err(e) => err = Some(e),
         }
+        for e in config_error.iter().chain(&profile_errors) {
+            log::warn!(error:% = e; "startup error");
+        }
+        // losing saved data matters more than a missing install
+        let err = config_error
+            .or_else(|| profile_errors.into_iter().next())
+            .or(err);
+        let mut app = App {
+            config: conf,
+            cfg,
+            readonly,
+            error: err,
+            profiles,
+            success: None,
+            champion_id: None,
+            connected: false,
+            retry_in: None,
+            client,
+            link: link.clone().unwrap_or_default(),
+            ..App::default()
+        };
+        app.resume_lock();
+        app.check_drift();
         (
-            App {
-                config: conf,
-                cfg,
-                readonly,
-                error: err,
-                profiles,
-                success: None,
-                champion_id: None,
-                connected: false,
-                retry_in: None,
-                client,
-                ..App::default()
-            },
-            Task::none(),
+            app,
+            Task::batch([
+                Task::perform(detect_installs(), Message::InstallsDetected),
+                link.map_or_else(Task::none, |link| Task::done(Message::FetchLink(link))),
+            ]),
         )
     }
 
     pub(crate) fn subscription(&self) -> Subscription<Message> {
-        Subscription::run(connect).map(Message::WebsocketEvent)
+        Subscription::batch([
+            Subscription::run(connect).map(Message::WebsocketEvent),
+            Subscription::run(instance::listen).map(Message::OtherInstance),
+            event::listen_with(|event, _status, _id| match event {
+                Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+                _ => None,
+            }),
+            match self.config.selected_install() {
+                Some(Install {
+                    applied: Some(_), ..
+                }) => iced::time::every(DRIFT_INTERVAL).map(|_| Message::CheckDrift),
+                _ => Subscription::none(),
+            },
+            match self.lock_guard {
+                Some(_) => iced::time::every(lock::CHECK_INTERVAL).map(|_| Message::EnforceLock),
+                None => Subscription::none(),
+            },
+        ])
     }
 }
+
+/// Looks for installs without blocking the window, it reads every drive.
+async fn detect_installs() -> Vec<Candidate> {
+    tokio::task::spawn_blocking(Config::detect_installs)
+        .await
+        .unwrap_or_default()
+}
@@ -2,12 +2,18 @@ use error::Error;
 
 use dialog::export_zip_path;
 
-use profile::Profile;
+use profile::{Profile, ProfileDraft};
 
-use iced::{clipboard, Task};
+use iced::{clipboard, window, Task};
 
 use message::Message;
 
+use paste::Progress;
+
+use super::{KeyPicker, RequestKind};
+
+use qr::ShareQr;
+
 use crate::*;
 
 use super::App;
@@ -15,59 +21,246 @@ use super::App;
 impl App {
     pub(crate) fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::WebsocketEvent(websocket::Event::Retrying(_)) => {}
+            // background messages keep the banner of whatever the user did last
+            Message::WebsocketEvent(websocket::Event::Retrying(_))
+            | Message::ShareRetrying(..)
+            | Message::RequestFinished(_)
+            | Message::EnforceLock
+            | Message::CheckDrift
+            | Message::InstallsDetected(_) => {}
             _ => {
                 self.error = None;
                 self.success = None;
             }
         };
 
+        // every error shown in the banner also ends up in the log
+        let shown = self.error.as_ref().map(Error::to_string);
+        let task = self.handle(message);
+        if let Some(error) = &self.error {
+            let error = error.to_string();
+            if shown.as_ref() != Some(&error) {
+                log::warn!(error:% = error; "showing error");
+            }
+        }
+        task
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::FindLocation => {
                 Task::perform(dialog::find_config_dialog(), Message::SetLocation)
             }
             Message::SetLocation(Ok(location)) => {
                 if self.set_cfg(&location).is_none() {
-                    self.config.set_path(Some(location));
+                    if let Err(e) = self.config.set_path(Some(location)) {
+                        self.error = Some(e);
+                    }
                 }
+                self.check_drift();
                 Task::none()
             }
             Message::SetLocation(Err(error)) => {
                 self.error = Some(error);
                 Task::none()
             }
+            Message::InstallsDetected(candidates) => {
+                match self.config.add_detected(&candidates) {
+                    Ok(true) => {
+                        let error = self.error.take();
+                        if let Some(path) = self.config.path().clone() {
+                            self.set_cfg(&path);
+                        }
+                        // finding an install only fixes the error about not having one
+                        if self.cfg.is_some() {
+                            self.error = error
+                                .filter(|e| !matches!(e, Error::MissingPath | Error::WrongPath));
+                        }
+                        self.check_drift();
+                    }
+                    Ok(false) => {}
+                    Err(e) => self.error = Some(e),
+                }
+                Task::none()
+            }
+            Message::RemoveInstall(path) => {
+                let selected = self.config.path().as_deref() == Some(path.as_path());
+                if let Err(e) = self.config.remove_install(&path) {
+                    self.error = Some(e);
+                } else if selected {
+                    match self.config.path().clone() {
+                        Some(path) => {
+                            self.set_cfg(&path);
+                        }
+                        None => {
+                            self.cfg = None;
+                            self.refresh_lock();
+                        }
+                    }
+                    self.check_drift();
+                }
+                Task::none()
+            }
             Message::SetReadonly(readonly) => {
                 self.readonly = readonly;
                 if let Some(c) = &self.cfg {
-                    c.set_readonly(readonly);
+                    if let Err(e) = c.set_readonly(readonly) {
+                        self.error = Some(e);
+                        self.readonly = c.readonly();
+                    }
+                }
+                self.refresh_lock();
+                Task::none()
+            }
+            Message::SetEnforceLock(enforce) => {
+                if let Err(e) = self.config.set_enforce_lock(enforce) {
+                    self.error = Some(e);
+                }
+                self.refresh_lock();
+                Task::none()
+            }
+            Message::EnforceLock => {
+                if let (Some(guard), Some(cfg)) = (&mut self.lock_guard, &self.cfg) {
+                    match guard.enforce(cfg) {
+                        Ok(true) => {
+                            log::info!(
+                                dir:% = cfg.game.parent().unwrap_or(&cfg.game).display();
+                                "restored locked settings"
+                            );
+                            self.success = Some("Restored locked settings".to_string());
+                            self.check_drift();
+                        }
+                        Ok(false) => {}
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::ShowLockedKeys => {
+                if let Some(cfg) = &self.cfg {
+                    match KeyPicker::new(cfg) {
+                        Ok(picker) => self.key_picker = Some(picker),
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::CloseLockedKeys => {
+                self.key_picker = None;
+                Task::none()
+            }
+            Message::FilterKeys(filter) => {
+                if let Some(picker) = &mut self.key_picker {
+                    picker.filter = filter;
+                }
+                Task::none()
+            }
+            Message::LockKey(key, locked) => {
+                let value = self
+                    .key_picker
+                    .as_ref()
+                    .and_then(|picker| picker.live.get(&key))
+                    .filter(|_| locked)
+                    .cloned();
+                if let Err(e) = self.config.set_locked_key(key, value) {
+                    self.error = Some(e);
                 }
+                self.refresh_lock();
                 Task::none()
             }
             Message::AddProfile => {
                 if let Some(cfg) = &self.cfg {
-                    let new_profile = Profile::new(cfg);
-                    self.profiles.push(new_profile);
+                    match Profile::new(cfg) {
+                        Ok(new_profile) => self.profiles.push(new_profile),
+                        Err(e) => self.error = Some(e),
+                    }
                 }
                 Task::none()
             }
             Message::RemoveProfile(s) => {
-                for (i, p) in self.profiles.iter().enumerate() {
-                    if p.name() == &s {
-                        p.delete();
-                        self.profiles.remove(i);
-                        break;
+                if let Some(i) = self.profiles.iter().position(|p| p.name() == &s) {
+                    match self.profiles[i].delete() {
+                        Ok(()) => {
+                            self.profiles.remove(i);
+                        }
+                        Err(e) => {
+                            self.error = Some(e);
+                            return Task::none();
+                        }
                     }
                 }
+                if let Err(e) = self.config.rename_applied(&s, None) {
+                    self.error = Some(e);
+                }
+                self.check_drift();
                 Task::none()
             }
             Message::UseProfile(prof) => {
-                if let Some(cfg) = &self.cfg {
-                    prof.copy_files(cfg);
-                    cfg.set_readonly(self.readonly);
-                    self.success = Some(format!("Using \"{}\"", prof.name()))
+                match self.use_profile(&prof) {
+                    Ok(()) => self.success = Some(format!("Using \"{}\"", prof.name())),
+                    Err(e) => self.error = Some(e),
                 }
                 Task::none()
             }
+            Message::CheckDrift => {
+                self.check_drift();
+                Task::none()
+            }
+            Message::SaveDrift => {
+                let (Some(name), Some(cfg)) = (self.drift.clone(), self.cfg.clone()) else {
+                    return Task::none();
+                };
+                if let Some(prof) = self.get_profile_from_name(&name) {
+                    match prof.save_from(&cfg) {
+                        Ok(()) => {
+                            let prof = prof.clone();
+                            if let Some(path) = self.config.path().clone() {
+                                self.record_applied(&path, cfg, &prof);
+                            }
+                            self.success = Some(format!("Saved changes to \"{name}\""));
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::DiscardDrift => {
+                let Some(name) = self.drift.clone() else {
+                    return Task::none();
+                };
+                if let Some(prof) = self.profiles.iter().find(|p| *p.name() == name).cloned() {
+                    match self.use_profile(&prof) {
+                        Ok(()) => self.success = Some(format!("Discarded changes to \"{name}\"")),
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::UseProfileEverywhere(prof) => {
+                let mut used = 0;
+                let mut skipped = Vec::new();
+                for install in self.config.installs().to_vec() {
+                    let Ok(cfg) = install.game_settings() else {
+                        log::warn!(install:% = install; "skipped install without settings");
+                        skipped.push(install.name);
+                        continue;
+                    };
+                    match prof.copy_files(&cfg) {
+                        Ok(()) => {
+                            self.record_applied(&install.path, cfg, &prof);
+                            used += 1;
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                let mut success = format!("Using \"{}\" on {used} installs", prof.name());
+                if !skipped.is_empty() {
+                    success += &format!(", skipped {} (no settings found)", skipped.join(", "));
+                }
+                self.success = Some(success);
+                self.refresh_lock();
+                Task::none()
+            }
             Message::Edit(name) => {
                 let prof = self.get_profile_from_name(&name).unwrap();
                 prof.edit_start();
@@ -79,8 +272,13 @@ impl App {
                 if let Err(e) = prof.edit_confirm() {
                     self.error = Some(e);
                 } else {
-                    self.success = Some(format!("Changed name to {}", &prof.name()));
+                    let new_name = prof.name().clone();
+                    self.success = Some(format!("Changed name to {new_name}"));
                     self.error = None;
+                    if let Err(e) = self.config.rename_applied(&name, Some(&new_name)) {
+                        self.error = Some(e);
+                    }
+                    self.check_drift();
                 }
                 Task::none()
             }
@@ -98,7 +296,7 @@ impl App {
             Message::SetExport(Ok((export_path, profile))) => {
                 match profile.zip(export_path) {
                     Ok(_) => self.success = Some("Exported profile".to_string()),
-                    Err(_) => self.error = Some(Error::ZipExport),
+                    Err(e) => self.error = Some(e),
                 };
                 Task::none()
             }
@@ -106,11 +304,24 @@ impl App {
                 self.error = Some(e);
                 Task::none()
             }
+            Message::ExportAll => {
+                Task::perform(dialog::export_bundle_path(), Message::SetExportAll)
+            }
+            Message::SetExportAll(Ok(export_path)) => {
+                match Profile::zip_bundle(&self.profiles, export_path) {
+                    Ok(_) => {
+                        self.success = Some(format!("Exported {} profiles", self.profiles.len()))
+                    }
+                    Err(e) => self.error = Some(e),
+                };
+                Task::none()
+            }
+            Message::SetExportAll(Err(e)) => {
+                self.error = Some(e);
+                Task::none()
+            }
             Message::SetImport(Ok(import_path)) => {
-                match Profile::from_zip(&import_path) {
-                    Ok(profile) => self.profiles.push(profile),
-                    Err(_) => self.error = Some(Error::ZipImport),
-                }
+                self.queue_imports(ProfileDraft::from_zip(&import_path));
                 Task::none()
             }
             Message::SetImport(Err(e)) => {
@@ -118,6 +329,24 @@ impl App {
                 Task::none()
             }
             Message::Import => Task::perform(dialog::import_zip_path(), Message::SetImport),
+            Message::ImportFolder => {
+                Task::perform(dialog::import_folder_path(), Message::SetImportFolder)
+            }
+            Message::SetImportFolder(Ok(import_path)) => {
+                self.queue_imports(ProfileDraft::from_dir(&import_path).map(|d| vec![d]));
+                Task::none()
+            }
+            Message::ImportFiles => {
+                Task::perform(dialog::import_files_path(), Message::SetImportFiles)
+            }
+            Message::SetImportFiles(Ok(import_paths)) => {
+                self.queue_imports(ProfileDraft::from_files(&import_paths).map(|d| vec![d]));
+                Task::none()
+            }
+            Message::SetImportFolder(Err(e)) | Message::SetImportFiles(Err(e)) => {
+                self.error = Some(e);
+                Task::none()
+            }
             Message::WebsocketEvent(event) => {
                 match event {
                     websocket::Event::Selected(x) => {
@@ -125,6 +354,7 @@ impl App {
 
                         if x > 0 {
                             self.champion_id = Some(x);
+                            log::info!(champion = x; "champion selected");
 
                             let mut profile =
                                 self.profiles.iter().find(|p| p.champion() == &Some(x));
@@ -132,22 +362,44 @@ impl App {
                                 profile = self.profiles.iter().find(|p| p.champion() == &Some(0));
                             }
 
-                            if let Some(prof) = profile {
-                                if let Some(cfg) = &self.cfg {
-                                    prof.copy_files(cfg);
+                            if let (Some(prof), Some((path, cfg))) =
+                                (profile.cloned(), self.swap_target())
+                            {
+                                match prof.copy_files(&cfg) {
+                                    Ok(()) => self.record_applied(&path, cfg, &prof),
+                                    Err(e) => self.error = Some(e),
                                 }
                             }
+                            self.refresh_lock();
                         }
                     }
                     websocket::Event::Connected => {
                         self.connected = true;
                         self.retry_in = None;
+                        self.connected_install = self
+                            .config
+                            .installs()
+                            .iter()
+                            .find(|i| i.is_running())
+                            .cloned();
+                        log::info!(
+                            install:? = self.connected_install.as_ref().map(|i| &i.name);
+                            "connected to the client"
+                        );
                     }
                     websocket::Event::Disconnected => {
                         self.connected = false;
                         self.retry_in = None;
+                        self.connected_install = None;
+                        log::info!("disconnected from the client");
+                        self.check_drift();
+                    }
+                    websocket::Event::Retrying(t) => {
+                        if self.retry_in.is_none() {
+                            log::debug!(retry_in = t; "client not found, retrying");
+                        }
+                        self.retry_in = Some(t);
                     }
-                    websocket::Event::Retrying(t) => self.retry_in = Some(t),
                 }
                 Task::none()
             }
@@ -170,7 +422,9 @@ impl App {
                     .find(|p| p.name() == &profile_name)
                     .unwrap();
 
-                profile.set_selected(option);
+                if let Err(e) = profile.set_selected(option) {
+                    self.error = Some(e);
+                }
 
                 Task::none()
             }
@@ -178,56 +432,200 @@ impl App {
                 self.success = Some("Copied link!".into());
                 clipboard::write::<Message>(link)
             }
-            // TODO: add .chain to task above later
-            Message::GenerateLink(content, profile_name) => {
-                self.success = Some("Generated link!".into());
-                Task::perform(paste::post(Arc::clone(&self.client), content), move |res| {
-                    if let Ok(res) = res {
-                        Message::PostLink(res, profile_name.clone())
-                    } else {
-                        Message::PostLinkError(res.err().unwrap().to_string())
+            Message::CopyCode(profile_name) => {
+                // read on click, the view would read both files on every redraw
+                let Some(profile) = self.profiles.iter().find(|p| *p.name() == profile_name) else {
+                    return Task::none();
+                };
+                match profile.paste_string() {
+                    Ok(content) => {
+                        self.success = Some("Copied code!".into());
+                        clipboard::write::<Message>(share_code::encode(&content))
                     }
-                })
+                    Err(e) => {
+                        self.error = Some(e);
+                        Task::none()
+                    }
+                }
+            }
+            Message::GenerateLink(profile_name) => {
+                let Some(profile) = self.profiles.iter().find(|p| *p.name() == profile_name) else {
+                    return Task::none();
+                };
+                let content = match profile.paste_string() {
+                    Ok(content) => content,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return Task::none();
+                    }
+                };
+                log::info!(profile:% = profile_name; "generating link");
+                let kind = RequestKind::Upload(profile_name.clone());
+                let progress = paste::post(self.client.clone(), self.config.share(), content);
+                self.start_request(
+                    kind.clone(),
+                    Task::run(progress, move |progress| match progress {
+                        Progress::Retrying {
+                            attempt,
+                            delay,
+                            error,
+                        } => Message::ShareRetrying(kind.clone(), attempt, delay, error),
+                        Progress::Finished(Ok(link)) => {
+                            Message::PostLink(link, profile_name.clone())
+                        }
+                        Progress::Finished(Err(error)) => Message::PostLinkError(error),
+                    }),
+                )
             }
             Message::PostLink(res, profile_name) => {
-                let profile = self
-                    .profiles
-                    .iter_mut()
-                    .find(|p| p.name() == &profile_name)
-                    .unwrap();
-
-                profile.set_link(res);
-
+                // the profile could have been renamed or removed while uploading
+                if let Some(profile) = self.profiles.iter_mut().find(|p| p.name() == &profile_name)
+                {
+                    match profile.set_link(res, self.config.share().backend().expiry_days()) {
+                        Ok(()) => {
+                            self.success = Some(format!("Generated link for \"{profile_name}\""))
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                }
                 Task::none()
             }
-            Message::PostLinkError(_err) => {
-                // println!("{err}");
+            Message::PostLinkError(error) => {
+                self.error = Some(Error::LinkUpload(error));
                 Task::none()
             }
             Message::ChangeLink(new_link) => {
                 self.link = new_link;
                 Task::none()
             }
-            Message::FetchLink(link) => Task::perform(
-                paste::get(Arc::clone(&self.client), link),
-                move |res| match res {
-                    Ok(content) => Message::AddProfileFromString(content),
-                    Err(_err) => Message::FetchError(Error::Import),
-                },
-            ),
+            Message::FetchLink(code) if share_code::is_code(&code) => {
+                match share_code::decode(&code) {
+                    Ok(content) => Task::done(Message::AddProfileFromString(content)),
+                    Err(e) => {
+                        self.error = Some(e);
+                        Task::none()
+                    }
+                }
+            }
+            Message::FetchLink(link) => {
+                log::info!(link:% = link; "downloading shared profile");
+                let progress = paste::get(self.client.clone(), self.config.share(), link);
+                self.start_request(
+                    RequestKind::Download,
+                    Task::run(progress, |progress| match progress {
+                        Progress::Retrying {
+                            attempt,
+                            delay,
+                            error,
+                        } => Message::ShareRetrying(RequestKind::Download, attempt, delay, error),
+                        Progress::Finished(Ok(content)) => Message::AddProfileFromString(content),
+                        Progress::Finished(Err(error)) => {
+                            Message::FetchError(Error::LinkDownload(error))
+                        }
+                    }),
+                )
+            }
+            Message::ShareRetrying(kind, attempt, delay, error) => {
+                log::warn!(
+                    request:? = kind,
+                    attempt,
+                    retry_in:? = delay,
+                    error:% = error;
+                    "share request failed"
+                );
+                if let Some(request) = self.requests.get_mut(&kind) {
+                    request.retry = Some((attempt, delay, error));
+                }
+                Task::none()
+            }
+            Message::CancelRequest(kind) | Message::RequestFinished(kind) => {
+                self.requests.remove(&kind);
+                Task::none()
+            }
             Message::AddProfileFromString(content) => {
-                let new_profile = Profile::from_string(content);
-                if new_profile.is_err() {
-                    self.error = new_profile.err();
-                    return Task::none();
+                self.queue_imports(ProfileDraft::from_string(&content).map(|d| vec![d]));
+                Task::none()
+            }
+            Message::AcceptImport => {
+                if !self.pending.is_empty() {
+                    match self.pending.remove(0).save() {
+                        Ok(profile) => {
+                            self.success = Some(format!("Imported \"{}\"", profile.name()));
+                            self.profiles.push(profile);
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
                 }
-                self.profiles.push(new_profile.unwrap());
+                self.refresh_preview();
                 Task::none()
             }
+            Message::RejectImport => {
+                if !self.pending.is_empty() {
+                    self.pending.remove(0);
+                }
+                self.refresh_preview();
+                Task::none()
+            }
+            Message::ShowQr(profile_name) => {
+                if let Some(profile) = self.profiles.iter().find(|p| *p.name() == profile_name) {
+                    match profile
+                        .qr_link()
+                        .ok_or(Error::QrTooLarge)
+                        .and_then(|link| ShareQr::new(&profile_name, link))
+                    {
+                        Ok(qr) => self.qr = Some(qr),
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::CloseQr => {
+                self.qr = None;
+                Task::none()
+            }
+            Message::SaveQr => match &self.qr {
+                Some(qr) => Task::perform(
+                    dialog::export_qr_path(qr.profile_name.clone()),
+                    Message::SetSaveQr,
+                ),
+                None => Task::none(),
+            },
+            Message::SetSaveQr(Ok(path)) => {
+                if let Some(qr) = &self.qr {
+                    match qr.save_png(&path) {
+                        Ok(_) => self.success = Some("Saved QR code".to_string()),
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                Task::none()
+            }
+            Message::SetSaveQr(Err(e)) => {
+                self.error = Some(e);
+                Task::none()
+            }
+            Message::FileDropped(path) => self.import_dropped(path),
             Message::FetchError(error) => {
                 self.error = Some(error);
                 Task::none()
             }
+            Message::OpenLog => {
+                if let Err(e) = logging::open_log() {
+                    self.error = Some(e);
+                }
+                Task::none()
+            }
+            Message::OtherInstance(link) => {
+                log::info!(link:? = link; "another instance was started");
+                let focus = window::get_oldest().and_then(window::gain_focus);
+                match link {
+                    Some(link) => Task::batch([focus, Task::done(Message::FetchLink(link))]),
+                    None => focus,
+                }
+            }
+            Message::CopyDiagnostics => {
+                self.success = Some("Copied diagnostics!".into());
+                clipboard::write::<Message>(self.diagnostics())
+            }
         }
     }
 }
@@ -6,8 +6,6 @@ use widget::SIZE_LEN;
 
 use iced::Length;
 
-use error::Error;
-
 use iced::widget::text;
 
 use widget::import_icon;
@@ -18,11 +16,23 @@ use iced::widget::Checkbox;
 
 use widget::open_icon;
 
+use widget::share_icon;
+
+use widget::trash_icon;
+
+use widget::{cancel_icon, confirm_icon};
+
+use preview::Preview;
+
+use qr::ShareQr;
+
+use iced::widget::image;
+
 use widget::icon_btn;
 
 use iced::widget::tooltip;
 
-use iced::widget::text_input;
+use iced::widget::{pick_list, text_input};
 
 use iced::widget::{column, row, Rule};
 
@@ -30,15 +40,29 @@ use crate::theme::Theme;
 
 use message::Message;
 
-use super::App;
+use super::{App, KeyPicker, RequestKind};
+
+/// Changed settings listed in the import preview, the rest are only counted.
+const PREVIEW_CHANGES: usize = 12;
+
+/// Unlocked settings listed in the locked settings panel, the filter narrows them down.
+const PICKER_KEYS: usize = 12;
+
+/// Height of the shown QR code, the saved PNG keeps its full size.
+const QR_SIZE: f32 = 320.0;
 
 impl App {
     pub(crate) fn view(&self) -> iced::Element<'_, Message, Theme> {
         let top_bar = self.view_top();
 
-        let profiles = self.view_profiles();
+        let profiles = match (&self.qr, &self.preview, &self.key_picker) {
+            (Some(qr), _, _) => self.view_qr(qr),
+            (None, Some(preview), _) => self.view_preview(preview),
+            (None, None, Some(picker)) => self.view_locked_keys(picker),
+            (None, None, None) => self.view_profiles(),
+        };
 
-        let connection_status = self.view_connection();
+        let connection_status = self.view_status();
 
         let import_bar = self.view_import();
 
@@ -48,10 +72,15 @@ impl App {
             profiles,
             connection_status,
             Rule::horizontal(0),
-            import_bar
         ]
         .spacing(10);
 
+        content = content.push_maybe(self.view_drift());
+
+        content = content.push_maybe(self.view_requests());
+
+        content = content.push(import_bar);
+
         content = content.push_maybe(self.view_error());
 
         content = content.push_maybe(self.view_success());
@@ -63,15 +92,233 @@ impl App {
         let mut profiles = column![].align_x(iced::Alignment::Center).spacing(15);
 
         for p in &self.profiles {
-            profiles = profiles.push(p.view_profile(&self.cfg));
+            let uploading = self.is_running(&RequestKind::Upload(p.name().clone()));
+            let installs = self.config.installs().len();
+            profiles = profiles.push(p.view_profile(&self.cfg, uploading, installs));
             profiles = profiles.push(Rule::horizontal(0));
         }
         profiles
     }
 
+    /// Incoming profile next to the live settings, with accept/reject buttons.
+    fn view_preview<'a>(
+        &'a self,
+        preview: &'a Preview,
+    ) -> iced::widget::Column<'a, Message, Theme> {
+        let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+
+        let mut title = format!("Import preview - {}", preview.name);
+        if self.pending.len() > 1 {
+            title = format!("{title} ({} more waiting)", self.pending.len() - 1);
+        }
+
+        let mut key_settings = column![row![
+            text("Setting").width(Length::Fill),
+            text("Imported").width(Length::FillPortion(1)),
+            text("Current").width(Length::FillPortion(1)),
+        ]]
+        .spacing(5);
+        for setting in &preview.key_settings {
+            key_settings = key_settings.push(row![
+                text(setting.label).width(Length::Fill),
+                text(value(&setting.value)).width(Length::FillPortion(1)),
+                text(value(&setting.live)).width(Length::FillPortion(1)),
+            ]);
+        }
+
+        let summary = if !preview.compared {
+            "Current settings not found, nothing to compare with".to_string()
+        } else if preview.changes.is_empty() {
+            "Same as the current settings".to_string()
+        } else {
+            format!(
+                "{} settings differ from the current settings:",
+                preview.changes.len()
+            )
+        };
+
+        let mut changes = column![text(summary)].spacing(5);
+        for change in preview.changes.iter().take(PREVIEW_CHANGES) {
+            changes = changes.push(text(format!(
+                "{}: {} -> {}",
+                change.key,
+                value(&change.live),
+                value(&change.value)
+            )));
+        }
+        if preview.changes.len() > PREVIEW_CHANGES {
+            changes = changes.push(text(format!(
+                "and {} more",
+                preview.changes.len() - PREVIEW_CHANGES
+            )));
+        }
+
+        let accept_btn = tooltip(
+            icon_btn(confirm_icon(), Some(Message::AcceptImport), colors::GREEN),
+            "Save as a profile",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let reject_btn = tooltip(
+            icon_btn(cancel_icon(), Some(Message::RejectImport), colors::RED),
+            "Discard",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let title_row = row![
+            text(title).size(20).width(Length::Fill),
+            accept_btn,
+            reject_btn
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        column![title_row, key_settings, Rule::horizontal(0), changes].spacing(10)
+    }
+
+    /// Live settings with a checkbox each to lock them, locked ones first.
+    fn view_locked_keys<'a>(
+        &'a self,
+        picker: &'a KeyPicker,
+    ) -> iced::widget::Column<'a, Message, Theme> {
+        let close_btn = tooltip(
+            icon_btn(cancel_icon(), Some(Message::CloseLockedKeys), colors::RED),
+            "Close",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let title_row = row![
+            text("Locked settings").size(20).width(Length::Fill),
+            close_btn
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        let filter = text_input("Filter, e.g. Input.ini or CameraLockMode", &picker.filter)
+            .on_input(Message::FilterKeys)
+            .padding(10);
+
+        let locked = self.config.locked_keys();
+        let needle = picker.filter.to_lowercase();
+        let unlocked: Vec<(&String, &String)> = picker
+            .live
+            .iter()
+            .filter(|(key, _)| !locked.contains_key(*key))
+            .filter(|(key, _)| key.to_lowercase().contains(&needle))
+            .collect();
+
+        let mut keys = column![].spacing(5);
+        let shown = locked.iter().map(|(key, value)| (key, value, true)).chain(
+            unlocked
+                .iter()
+                .take(PICKER_KEYS)
+                .map(|(key, value)| (*key, *value, false)),
+        );
+        for (key, value, is_locked) in shown {
+            let checkbox = Checkbox::new(key.as_str(), is_locked)
+                .on_toggle(|lock| Message::LockKey(key.clone(), lock))
+                .width(Length::Fill);
+            keys = keys.push(
+                row![checkbox, text(value.as_str()).width(Length::Shrink)]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center),
+            );
+        }
+        if unlocked.len() > PICKER_KEYS {
+            keys = keys.push(text(format!(
+                "and {} more, narrow down the filter",
+                unlocked.len() - PICKER_KEYS
+            )));
+        }
+
+        column![
+            title_row,
+            text("Locked settings are put back after every game, the game keeps the rest."),
+            filter,
+            keys
+        ]
+        .spacing(10)
+    }
+
+    /// QR code of a profile link or share code, with buttons to save it as PNG or close it.
+    fn view_qr<'a>(&'a self, qr: &'a ShareQr) -> iced::widget::Column<'a, Message, Theme> {
+        let save_btn = tooltip(
+            icon_btn(share_icon(), Some(Message::SaveQr), colors::BLUE),
+            "Save as .png",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let close_btn = tooltip(
+            icon_btn(cancel_icon(), Some(Message::CloseQr), colors::RED),
+            "Close",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let title_row = row![
+            text(format!("QR code - {}", qr.profile_name))
+                .size(20)
+                .width(Length::Fill),
+            save_btn,
+            close_btn
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        let content = if qr.content.starts_with("http") {
+            qr.content.clone()
+        } else {
+            "Share code".to_string()
+        };
+
+        column![
+            title_row,
+            container(image(qr.handle.clone()).height(Length::Fixed(QR_SIZE)))
+                .center_x(Length::Fill),
+            container(text(content)).center_x(Length::Fill)
+        ]
+        .spacing(10)
+    }
+
+    /// Connection status with the log buttons for bug reports.
+    fn view_status(&self) -> iced::widget::Row<'_, Message, Theme> {
+        let log_btn = tooltip(
+            icon_btn(text("L").into(), Some(Message::OpenLog), colors::BLUE),
+            "Open the log file",
+            tooltip::Position::Top,
+        )
+        .class(theme::Container::Tooltip);
+
+        let diagnostics_btn = tooltip(
+            icon_btn(
+                text("D").into(),
+                Some(Message::CopyDiagnostics),
+                colors::BLUE,
+            ),
+            "Copy diagnostics for a bug report",
+            tooltip::Position::Top,
+        )
+        .class(theme::Container::Tooltip);
+
+        row![
+            self.view_connection().width(Length::Fill),
+            log_btn,
+            diagnostics_btn
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
+    }
+
     fn view_connection(&self) -> iced::widget::Text<'_, Theme> {
         let connection_status = text(if self.connected {
-            let mut txt = "Connected.".to_string();
+            let mut txt = match &self.connected_install {
+                Some(install) => format!("Connected to {}.", install.name),
+                None => "Connected.".to_string(),
+            };
             if self.champion_id.is_some() {
                 txt = format!(
                     "Selected champion: {}",
@@ -94,6 +341,62 @@ impl App {
         connection_status
     }
 
+    /// Shown when the live settings changed since a profile was used, e.g. after a game.
+    fn view_drift(&self) -> Option<iced::widget::Row<'_, Message, Theme>> {
+        let name = self.drift.as_ref()?;
+
+        let save_btn = tooltip(
+            icon_btn(confirm_icon(), Some(Message::SaveDrift), colors::GREEN),
+            "Save changes to the profile",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let discard_btn = tooltip(
+            icon_btn(cancel_icon(), Some(Message::DiscardDrift), colors::RED),
+            "Discard changes and use the profile again",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        Some(
+            row![
+                text(format!("\"{name}\" modified since applied")).width(Length::Fill),
+                save_btn,
+                discard_btn
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+        )
+    }
+
+    /// One row with a cancel button for every running paste service request.
+    fn view_requests(&self) -> Option<iced::widget::Column<'_, Message, Theme>> {
+        if self.requests.is_empty() {
+            return None;
+        }
+        let mut requests = column![].spacing(10);
+        for (kind, request) in &self.requests {
+            let cancel_btn = tooltip(
+                icon_btn(
+                    cancel_icon(),
+                    Some(Message::CancelRequest(kind.clone())),
+                    colors::RED,
+                ),
+                "Cancel",
+                tooltip::Position::Bottom,
+            )
+            .class(theme::Container::Tooltip);
+
+            requests = requests.push(
+                row![text(request.status(kind)).width(Length::Fill), cancel_btn]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center),
+            );
+        }
+        Some(requests)
+    }
+
     fn view_success(&self) -> Option<iced::widget::Container<'_, Message, Theme>> {
         if let Some(success) = &self.success {
             let success_text = text(format!("Success! - {}", success))
@@ -110,22 +413,7 @@ impl App {
 
     fn view_error(&self) -> Option<iced::widget::Container<'_, Message, Theme>> {
         if let Some(e) = &self.error {
-            let error_str = match e {
-                Error::DialogClosed => "Dialog closed without choosing the folder",
-                Error::WrongPath => {
-                    "Wrong path (folder should look like \".../Riot Games/League of Legends/\""
-                }
-                Error::MissingPath => {
-                    "Missing path (choose the League of Legends install directory)"
-                }
-                Error::NameTaken => "Name is taken",
-                Error::ZipExport => "Error exporting profile",
-                Error::ZipImport => "Error importing profile",
-                Error::ChampionTaken => "Another profile already handles that champion",
-                Error::Import => "Error importing/generating a profile. Try again in a few seconds.",
-            };
-
-            let error_text = text(error_str).size(20).class(theme::Text::Error);
+            let error_text = text(e.to_string()).size(20).class(theme::Text::Error);
             let error_container = container(error_text)
                 .class(crate::theme::Container::Error)
                 .center_x(Length::Fill)
@@ -137,30 +425,49 @@ impl App {
     }
 
     fn view_import(&self) -> iced::Element<'_, Message, Theme> {
-        let text_inp = text_input("Import link", &self.link)
+        let text_inp = text_input("Import link or code", &self.link)
             .padding(10)
             .on_input(Message::ChangeLink)
             .on_paste(Message::ChangeLink);
 
+        let downloading = self.is_running(&RequestKind::Download);
         let import_button = tooltip(
             icon_btn(
                 add_icon(),
-                Some(Message::FetchLink(self.link.clone())),
+                (!downloading).then(|| Message::FetchLink(self.link.clone())),
                 colors::BLUE,
             ),
-            "Copy link",
+            if downloading {
+                "Downloading profile..."
+            } else {
+                "Import link or code"
+            },
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let folder_button = tooltip(
+            icon_btn(open_icon(), Some(Message::ImportFolder), colors::BLUE),
+            "Import profile from a \"Config\" folder",
             tooltip::Position::Bottom,
         )
         .class(theme::Container::Tooltip);
 
-        row![text_inp, import_button]
+        let files_button = tooltip(
+            icon_btn(import_icon(), Some(Message::ImportFiles), colors::BLUE),
+            "Import profile from game.cfg and PersistedSettings.json",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        row![text_inp, import_button, folder_button, files_button]
             .spacing(10)
             .align_y(iced::Alignment::Center)
             .into()
     }
 
     fn view_top(&self) -> iced::widget::Column<'_, Message, Theme> {
-        let config_path = text_input("Config not found", self.config.path_to_str()).padding(10);
+        let config_path = text_input("Config not found", &self.config.path_to_str()).padding(10);
         let location_btn = tooltip(
             icon_btn(open_icon(), Message::FindLocation.into(), colors::GOLD),
             "Find \"League of Legends\" directory",
@@ -182,14 +489,65 @@ impl App {
 
         let import_profile = tooltip(
             icon_btn(import_icon(), Message::Import.into(), colors::BLUE),
-            "Import profile from .zip file",
+            "Import profile or bundle from .zip file",
             tooltip::Position::Bottom,
         )
         .class(theme::Container::Tooltip);
 
-        let location = row![config_path, location_btn, add_profile, import_profile]
-            .align_y(iced::Alignment::Center)
-            .spacing(10);
+        let mut export_all = icon_btn(share_icon(), None, colors::BLUE);
+        if !self.profiles.is_empty() {
+            export_all = export_all.on_press(Message::ExportAll);
+        }
+
+        let export_all = tooltip(
+            export_all,
+            "Export all profiles to a single .zip file",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let location = row![
+            config_path,
+            location_btn,
+            add_profile,
+            import_profile,
+            export_all
+        ]
+        .align_y(iced::Alignment::Center)
+        .spacing(10);
+
+        let mut top = column![location].spacing(10);
+
+        if self.config.installs().len() > 1 {
+            let installs = pick_list(
+                self.config.installs(),
+                self.config.selected_install().cloned(),
+                |install| Message::SetLocation(Ok(install.path)),
+            )
+            .placeholder("Choose install")
+            .width(Length::Fill);
+            let installs = tooltip(
+                installs,
+                "Install that profiles are added from and used on",
+                tooltip::Position::Bottom,
+            )
+            .class(theme::Container::Tooltip);
+            let remove = tooltip(
+                icon_btn(
+                    trash_icon(),
+                    self.config.path().clone().map(Message::RemoveInstall),
+                    colors::RED,
+                ),
+                "Remove install from the list, its settings are left alone",
+                tooltip::Position::Bottom,
+            )
+            .class(theme::Container::Tooltip);
+            top = top.push(
+                row![installs, remove]
+                    .align_y(iced::Alignment::Center)
+                    .spacing(10),
+            );
+        }
 
         let mut cb = Checkbox::new("Lock settings", self.readonly);
         if self.cfg.is_some() {
@@ -203,6 +561,34 @@ impl App {
         )
         .class(theme::Container::Tooltip);
 
-        column![location, cb].spacing(10)
+        let mut enforce = Checkbox::new("Enforce lock", self.config.enforce_lock());
+        if self.cfg.is_some() {
+            enforce = enforce.on_toggle(Message::SetEnforceLock)
+        }
+
+        let enforce = tooltip(
+            enforce,
+            "Locked settings are put back whenever they change, e.g. after a game",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let mut keys_btn = icon_btn(text("K").into(), None, colors::BLUE);
+        if self.cfg.is_some() {
+            keys_btn = keys_btn.on_press(Message::ShowLockedKeys);
+        }
+
+        let keys_btn = tooltip(
+            keys_btn,
+            "Lock only some settings, e.g. keybinds",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        top.push(
+            row![cb, enforce, keys_btn]
+                .spacing(20)
+                .align_y(iced::Alignment::Center),
+        )
     }
 }
@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current UTC date formatted as `YYYY-MM-DD`.
+pub(crate) fn today() -> String {
+    date_from_unix(now())
+}
+
+/// Seconds since the unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) fn date_from_unix(secs: u64) -> String {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// UTC time formatted as `YYYY-MM-DDTHH:MM:SSZ`.
+pub(crate) fn timestamp_from_unix(secs: u64) -> String {
+    let time = secs % 86400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date_from_unix(secs),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_to_date() {
+        assert_eq!(date_from_unix(0), "1970-01-01");
+        assert_eq!(date_from_unix(951_782_400), "2000-02-29");
+        assert_eq!(date_from_unix(1_729_296_000), "2024-10-19");
+    }
+
+    #[test]
+    fn unix_to_timestamp() {
+        assert_eq!(timestamp_from_unix(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp_from_unix(1_729_346_589), "2024-10-19T14:03:09Z");
+    }
+}
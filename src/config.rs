@@ -1,29 +1,201 @@
+mod detect;
+
+pub use detect::Candidate;
+
 use std::{
-    fs::{self, OpenOptions},
-    io::BufReader,
+    borrow::Cow,
+    collections::BTreeMap,
+    env, fmt, fs,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    error::Error,
+    game_settings::{GameSettings, SettingsFiles},
+    paste::ShareService,
+    versioned::{self, Migration, Versioned},
+};
+
+const DATA_DIR_FLAG: &str = "--data-dir";
+
+const DATA_DIR_ENV: &str = "LCM_DATA_DIR";
+
+const PORTABLE_MARKER: &str = "portable";
+
+/// Data folder in portable mode, next to the executable.
+const PORTABLE_DIR: &str = "data";
 
-use crate::game_settings::GameSettings;
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Index `n` upgrades a version `n` config file to version `n + 1`.
+const MIGRATIONS: [Migration; 1] = [Config::migrate_installs];
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Config {
+    /// The selected install.
     path: Option<PathBuf>,
+    #[serde(default)]
+    installs: Vec<Install>,
+    /// Installs removed from the list, detection doesn't add them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed_installs: Vec<PathBuf>,
+    #[serde(default)]
+    share: ShareService,
+    /// Restore the locked settings whenever something changes them.
+    #[serde(default)]
+    enforce_lock: bool,
+    /// Settings kept at these values, keys as in `SettingsFiles::values`.
+    #[serde(default)]
+    locked_keys: BTreeMap<String, String>,
+    /// Why the config file couldn't be read, nothing is saved over it then.
+    #[serde(skip)]
+    read_error: Option<Error>,
+}
+
+/// A League of Legends folder, e.g. Live and PBE installed side by side.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Install {
+    pub name: String,
+    pub path: PathBuf,
+    /// Profile used on this install last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied: Option<Applied>,
+    /// Settings kept while the lock is enforced, saved so changes made while the app was
+    /// closed are put back too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<SettingsFiles>,
+}
+
+/// A profile used on an install, with the hash of the live files right after.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Applied {
+    pub profile: String,
+    pub hash: String,
+}
+
+impl Install {
+    /// Returns whether the applied profile was `old_name`.
+    fn rename_applied(&mut self, old_name: &str, new_name: Option<&str>) -> bool {
+        if self.applied.as_ref().is_none_or(|a| a.profile != old_name) {
+            return false;
+        }
+        self.applied = new_name.zip(self.applied.take()).map(|(name, a)| Applied {
+            profile: name.to_string(),
+            ..a
+        });
+        true
+    }
+
+    /// Live and PBE are named after their default folders, anything else after its folder.
+    fn default_name(path: &Path) -> String {
+        let folder = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if folder == "League of Legends" {
+            "Live".to_string()
+        } else if folder.contains("PBE") {
+            "PBE".to_string()
+        } else {
+            folder.to_string()
+        }
+    }
+
+    /// The `lockfile` only exists while this install's client is running.
+    pub fn is_running(&self) -> bool {
+        self.path.join("lockfile").exists()
+    }
+
+    pub fn game_settings(&self) -> Result<GameSettings, crate::error::Error> {
+        GameSettings::from_path(&Config::game_settings_path(&self.path))
+    }
+}
+
+impl fmt::Display for Install {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.name, self.path.display())
+    }
 }
 
 //static methods
 impl Config {
+    /// Picks the folder profiles and the config file are kept in, the first of:
+    /// `--data-dir <folder>`, the `LCM_DATA_DIR` environment variable, a `data` folder next to
+    /// the executable in portable mode and the user's data folder.
+    ///
+    /// Has to run before anything else uses the data folder.
+    pub fn init_data_dir(args: impl IntoIterator<Item = String>) {
+        let dir = Self::data_dir_from_args(args)
+            .or_else(|| {
+                env::var_os(DATA_DIR_ENV)
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+            })
+            .unwrap_or_else(Self::default_data_dir);
+        let _ = DATA_DIR.set(dir);
+    }
+
     pub fn get_config_dir() -> PathBuf {
-        let proj_dir =
-            ProjectDirs::from("", "", "league_config_manager").expect("This should always resolve");
-        let dir = proj_dir.data_dir().to_path_buf();
-        fs::create_dir_all(&dir).expect("Should always create these dirs");
+        let dir = DATA_DIR.get_or_init(Self::default_data_dir).clone();
+        // a folder that can't be created shows up as errors of whatever uses it next
+        let _ = fs::create_dir_all(&dir);
         dir
     }
 
+    /// Portable mode is on when a `portable` file is next to the executable, everything is
+    /// kept next to it then and nothing is registered with the system.
+    pub fn is_portable() -> bool {
+        Self::portable_dir().is_some()
+    }
+
+    /// Whether the data folder was changed with `--data-dir` or `LCM_DATA_DIR`, e.g. for test
+    /// data, `lcm://` links are left to the real install then.
+    pub fn has_data_dir_override() -> bool {
+        DATA_DIR
+            .get()
+            .is_some_and(|dir| *dir != Self::default_data_dir())
+    }
+
+    fn portable_dir() -> Option<PathBuf> {
+        let exe = env::current_exe().ok()?;
+        let exe_dir = exe.parent()?;
+        exe_dir
+            .join(PORTABLE_MARKER)
+            .exists()
+            .then(|| exe_dir.join(PORTABLE_DIR))
+    }
+
+    fn default_data_dir() -> PathBuf {
+        Self::portable_dir()
+            .or_else(|| {
+                ProjectDirs::from("", "", "league_config_manager")
+                    .map(|dirs| dirs.data_dir().to_path_buf())
+            })
+            .unwrap_or_else(|| PathBuf::from(PORTABLE_DIR))
+    }
+
+    /// Reads `--data-dir <folder>` or `--data-dir=<folder>`.
+    fn data_dir_from_args(args: impl IntoIterator<Item = String>) -> Option<PathBuf> {
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == DATA_DIR_FLAG {
+                return args.next().map(PathBuf::from);
+            }
+            if let Some(dir) = arg
+                .strip_prefix(DATA_DIR_FLAG)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                return Some(PathBuf::from(dir));
+            }
+        }
+        None
+    }
+
     pub fn get_config_path() -> PathBuf {
         let dir = Self::get_config_dir();
         dir.join("config.json")
@@ -31,76 +203,329 @@ impl Config {
 }
 
 impl Config {
-    pub fn new() -> Self {
-        let config_path = Self::get_config_path();
-        let config_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(config_path)
-            .expect("Can't open config file");
-        let reader = BufReader::new(config_file);
-        let mut config: Config = serde_json::from_reader(reader).unwrap_or_else(|_| {
-            let cfg_path = Self::try_cfg();
-            let config = Config { path: cfg_path };
-            config.write_config();
-            config
-        });
+    /// Loads the config file, an unreadable one is kept as a backup and reported instead of
+    /// being overwritten without a word. One that can't be read at all is left alone and the
+    /// defaults are used without saving them.
+    pub fn new() -> (Self, Option<Error>) {
+        let path = Self::get_config_path();
+        let (config, mut error, changed) = match versioned::read(&path, &MIGRATIONS) {
+            Versioned::Read(config) => (config, None, false),
+            Versioned::Missing => (Config::default(), None, true),
+            Versioned::Unreadable(backup) => {
+                (Config::default(), Some(Error::ConfigReset(backup)), true)
+            }
+            Versioned::Failed(e) => {
+                let error = Error::read(&path)(e);
+                let config = Config {
+                    read_error: Some(error.clone()),
+                    ..Config::default()
+                };
+                (config, Some(error), false)
+            }
+        };
+
+        if changed {
+            if let Err(e) = config.write_config() {
+                error.get_or_insert(e);
+            }
+        }
+        (config, error)
+    }
+
+    /// Version 1 added `installs`, older configs only have the selected `path`. Some were
+    /// written with `installs` before versioning, those are kept.
+    fn migrate_installs(config: &mut Map<String, Value>) {
+        if let Some(path) = config.get("path").and_then(Value::as_str) {
+            let install = json!({
+                "name": Install::default_name(Path::new(path)),
+                "path": path,
+            });
+            config.entry("installs").or_insert_with(|| json!([install]));
+        }
+    }
+
+    /// Remembers installs found by [`Config::detect_installs`] except removed ones, and picks
+    /// the best of them when the selected install has no settings. Returns whether the
+    /// selected install changed.
+    pub fn add_detected(&mut self, detected: &[Candidate]) -> Result<bool, Error> {
+        let (changed, selected) = self.merge_detected(detected);
+        if changed {
+            self.update()?;
+        }
+        Ok(selected)
+    }
+
+    /// Returns whether anything changed and whether the selected install did.
+    fn merge_detected(&mut self, detected: &[Candidate]) -> (bool, bool) {
+        let detected: Vec<&Candidate> = detected
+            .iter()
+            .filter(|c| !self.removed_installs.contains(&c.path))
+            .collect();
+
         // TODO: ovo nije dobro uopste uradi kad budes odspavao
-        if let Some(path) = &config.path {
-            if GameSettings::from_path(&Self::game_settings_path(path)).is_err() {
-                config.path = Self::try_cfg();
+        let valid = self
+            .path
+            .as_ref()
+            .is_some_and(|path| GameSettings::from_path(&Self::game_settings_path(path)).is_ok());
+        let selected = match detected.first() {
+            Some(best) if !valid => {
+                self.path = Some(best.path.clone());
+                true
+            }
+            _ => false,
+        };
+
+        let mut changed = selected;
+        for candidate in detected {
+            changed |= self.add_install(candidate.path.clone());
+        }
+        (changed, selected)
+    }
+
+    /// Drops the install at `path` from the list, selecting the first one left if it was
+    /// selected.
+    pub fn remove_install(&mut self, path: &Path) -> Result<(), Error> {
+        self.installs.retain(|i| i.path != path);
+        if !self.removed_installs.iter().any(|p| p == path) {
+            self.removed_installs.push(path.to_path_buf());
+        }
+        if self.path.as_deref() == Some(path) {
+            self.path = self.installs.first().map(|i| i.path.clone());
+        }
+        self.update()
+    }
+
+    pub fn installs(&self) -> &[Install] {
+        &self.installs
+    }
+
+    pub fn selected_install(&self) -> Option<&Install> {
+        self.installs
+            .iter()
+            .find(|i| Some(&i.path) == self.path.as_ref())
+    }
+
+    /// Records the profile used on the install at `path`, `None` forgets it.
+    pub fn set_applied(&mut self, path: &Path, applied: Option<Applied>) -> Result<(), Error> {
+        match self.installs.iter_mut().find(|i| i.path == path) {
+            Some(install) => {
+                install.applied = applied;
+                self.update()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Saves the settings locked on the install at `path`, `None` once the lock is off.
+    pub fn set_locked(&mut self, path: &Path, locked: Option<SettingsFiles>) -> Result<(), Error> {
+        match self.installs.iter_mut().find(|i| i.path == path) {
+            Some(install) if install.locked != locked => {
+                install.locked = locked;
+                self.update()
             }
+            _ => Ok(()),
+        }
+    }
+
+    /// Follows a profile being renamed to `new_name`, or removed with `None`.
+    pub fn rename_applied(&mut self, old_name: &str, new_name: Option<&str>) -> Result<(), Error> {
+        let mut changed = false;
+        for install in &mut self.installs {
+            changed |= install.rename_applied(old_name, new_name);
         }
-        config
+        if changed {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    /// Adds `path` under a free name unless it's already there, returns whether it was added.
+    fn add_install(&mut self, path: PathBuf) -> bool {
+        if self.installs.iter().any(|i| i.path == path) {
+            return false;
+        }
+        let default_name = Install::default_name(&path);
+        let mut name = default_name.clone();
+        let mut i = 2;
+        while self.installs.iter().any(|install| install.name == name) {
+            name = format!("{default_name} {i}");
+            i += 1;
+        }
+        self.installs.push(Install {
+            name,
+            path,
+            applied: None,
+            locked: None,
+        });
+        true
     }
 
     pub fn path(&self) -> &Option<PathBuf> {
         &self.path
     }
 
-    pub fn path_to_str(&self) -> &str {
+    pub fn path_to_str(&self) -> Cow<'_, str> {
         match &self.path {
-            Some(path) => path.to_str().expect("Should always work"),
-            _ => "",
+            Some(path) => path.to_string_lossy(),
+            _ => Cow::Borrowed(""),
         }
     }
 
-    pub fn set_path(&mut self, cfg_path: Option<PathBuf>) {
+    pub fn share(&self) -> &ShareService {
+        &self.share
+    }
+
+    pub fn enforce_lock(&self) -> bool {
+        self.enforce_lock
+    }
+
+    pub fn set_enforce_lock(&mut self, enforce: bool) -> Result<(), Error> {
+        self.enforce_lock = enforce;
+        self.update()
+    }
+
+    pub fn locked_keys(&self) -> &BTreeMap<String, String> {
+        &self.locked_keys
+    }
+
+    /// Locks `key` to `value`, or unlocks it with `None`.
+    pub fn set_locked_key(&mut self, key: String, value: Option<String>) -> Result<(), Error> {
+        match value {
+            Some(value) => self.locked_keys.insert(key, value),
+            None => self.locked_keys.remove(&key),
+        };
+        self.update()
+    }
+
+    pub fn set_path(&mut self, cfg_path: Option<PathBuf>) -> Result<(), Error> {
+        if let Some(path) = &cfg_path {
+            self.removed_installs.retain(|p| p != path);
+            self.add_install(path.clone());
+        }
         self.path = cfg_path;
-        self.update();
+        self.update()
     }
 
     /// Write config file
-    pub fn write_config(&self) {
-        let config_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(Self::get_config_path())
-            .expect("Can't open config file");
-        let writer = std::io::BufWriter::new(config_file);
-        serde_json::to_writer(writer, self).expect("Couldn't write the config");
-    }
-
-    pub fn update(&self) {
-        self.write_config();
-    }
-
-    fn try_cfg() -> Option<PathBuf> {
-        let folders = ["C:\\Riot Games\\League of Legends"].map(PathBuf::from);
-        for folder in folders {
-            let folder_new = folder.join("Config");
-            let cfg = GameSettings::from_path(&folder_new);
-            if cfg.is_ok() {
-                return Some(folder);
-            }
+    pub fn write_config(&self) -> Result<(), Error> {
+        if let Some(e) = &self.read_error {
+            return Err(e.clone());
         }
-        None
+        let path = Self::get_config_path();
+        versioned::write(&path, self, &MIGRATIONS).map_err(Error::write(&path))
+    }
+
+    pub fn update(&self) -> Result<(), Error> {
+        self.write_config()
+    }
+
+    /// League of Legends installs found on this machine, best candidates first. Reads every
+    /// drive, so it's run in the background.
+    pub fn detect_installs() -> Vec<Candidate> {
+        detect::candidates()
     }
 
     pub fn game_settings_path(path: &Path) -> PathBuf {
         path.join("Config")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_data_dir_flag() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Config::data_dir_from_args(args(&["lcm", "--data-dir", "E:/lcm"])),
+            Some(PathBuf::from("E:/lcm"))
+        );
+        assert_eq!(
+            Config::data_dir_from_args(args(&["lcm", "lcm://import?code=x", "--data-dir=test"])),
+            Some(PathBuf::from("test"))
+        );
+        assert_eq!(
+            Config::data_dir_from_args(args(&["lcm", "--data-dirx"])),
+            None
+        );
+    }
+
+    #[test]
+    fn migrates_single_path_configs() {
+        let mut config = json!({ "path": "C:/Riot Games/League of Legends" });
+        Config::migrate_installs(config.as_object_mut().unwrap());
+        let config: Config = serde_json::from_value(config).unwrap();
+        assert_eq!(config.installs()[0].name, "Live");
+        assert_eq!(config.selected_install(), config.installs().first());
+
+        let installs = json!([
+            { "name": "Live", "path": "C:/Riot Games/League of Legends" },
+            { "name": "PBE", "path": "C:/Riot Games/League of Legends (PBE)" },
+        ]);
+        let mut config = json!({
+            "path": "C:/Riot Games/League of Legends",
+            "installs": installs.clone(),
+        });
+        Config::migrate_installs(config.as_object_mut().unwrap());
+        assert_eq!(config["installs"], installs);
+    }
+
+    #[test]
+    fn installs_get_free_names() {
+        let mut config = Config::default();
+        assert!(config.add_install(PathBuf::from("C:/Riot Games/League of Legends")));
+        assert!(config.add_install(PathBuf::from("C:/Riot Games/League of Legends (PBE)")));
+        assert!(config.add_install(PathBuf::from("D:/League of Legends")));
+        assert!(!config.add_install(PathBuf::from("D:/League of Legends")));
+
+        let names: Vec<&str> = config.installs().iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Live", "PBE", "Live 2"]);
+    }
+
+    #[test]
+    fn removed_installs_are_not_detected_again() {
+        let candidate = |path: &str| Candidate {
+            path: PathBuf::from(path),
+            source: detect::Source::CommonFolder,
+        };
+        let mut config = Config {
+            removed_installs: vec![PathBuf::from("C:/Riot Games/League of Legends")],
+            ..Config::default()
+        };
+
+        let detected = [
+            candidate("C:/Riot Games/League of Legends"),
+            candidate("C:/Riot Games/League of Legends (PBE)"),
+        ];
+        assert_eq!(config.merge_detected(&detected[..1]), (false, false));
+        assert_eq!(config.merge_detected(&detected), (true, true));
+        assert_eq!(config.path, Some(detected[1].path.clone()));
+        let names: Vec<&str> = config.installs().iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["PBE"]);
+    }
+
+    #[test]
+    fn applied_profiles_follow_renames() {
+        let mut install = Install {
+            name: "Live".to_string(),
+            path: PathBuf::from("C:/Riot Games/League of Legends"),
+            applied: Some(Applied {
+                profile: "mid".to_string(),
+                hash: "1234abcd".to_string(),
+            }),
+            locked: None,
+        };
+
+        assert!(!install.rename_applied("top", Some("jungle")));
+        assert!(install.rename_applied("mid", Some("adc")));
+        let applied = install.applied.as_ref().unwrap();
+        assert_eq!(
+            (applied.profile.as_str(), applied.hash.as_str()),
+            ("adc", "1234abcd")
+        );
+
+        assert!(install.rename_applied("adc", None));
+        assert_eq!(install.applied, None);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::game_settings::GameSettings;
+
+use super::Config;
+
+/// Folders installs usually end up in, relative to a drive.
+const COMMON_FOLDERS: [&str; 3] = [
+    "Riot Games/League of Legends",
+    "Program Files/Riot Games/League of Legends",
+    "Games/Riot Games/League of Legends",
+];
+
+/// Lists every Riot Client install, relative to `C:`.
+const RIOT_CLIENT_INSTALLS: &str = "ProgramData/Riot Games/RiotClientInstalls.json";
+
+const PRODUCT_SETTINGS: &str = "ProgramData/Riot Games/Metadata/league_of_legends.live/league_of_legends.live.product_settings.yaml";
+
+/// How an install was found, sources that are more likely to be right come first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    RiotClient,
+    ProductSettings,
+    CommonFolder,
+}
+
+/// A League of Legends folder with settings files in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub source: Source,
+}
+
+/// A Windows file system, either the real one or a Wine prefix.
+struct WindowsRoot {
+    /// `drive_c` of a Wine prefix, `None` for the real file system.
+    drive_c: Option<PathBuf>,
+}
+
+impl WindowsRoot {
+    /// Local path of `windows_path`, paths outside of `C:` can't be followed inside a prefix.
+    fn resolve(&self, windows_path: &str) -> Option<PathBuf> {
+        let Some(drive_c) = &self.drive_c else {
+            return Some(PathBuf::from(windows_path));
+        };
+        let (drive, rest) = windows_path.split_once(':')?;
+        if !drive.eq_ignore_ascii_case("c") {
+            return None;
+        }
+        let rest = rest.replace('\\', "/");
+        Some(drive_c.join(rest.trim_start_matches('/')))
+    }
+
+    /// Drives to look for common folders on.
+    fn drives(&self) -> Vec<PathBuf> {
+        match &self.drive_c {
+            Some(drive_c) => vec![drive_c.clone()],
+            None if cfg!(windows) => ('C'..='Z')
+                .map(|letter| PathBuf::from(format!("{letter}:\\")))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn candidates(&self) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        let mut push = |path: Option<PathBuf>, source| {
+            if let Some(path) = path {
+                candidates.push(Candidate { path, source });
+            }
+        };
+
+        if let Some(content) = self.read(RIOT_CLIENT_INSTALLS) {
+            for path in parse_riot_client_installs(&content) {
+                push(self.resolve(&path), Source::RiotClient);
+            }
+        }
+        if let Some(content) = self.read(PRODUCT_SETTINGS) {
+            push(
+                parse_product_settings(&content).and_then(|path| self.resolve(&path)),
+                Source::ProductSettings,
+            );
+        }
+        for drive in self.drives() {
+            for folder in COMMON_FOLDERS {
+                push(Some(drive.join(folder)), Source::CommonFolder);
+            }
+        }
+        candidates
+    }
+
+    fn read(&self, path_on_c: &str) -> Option<String> {
+        fs::read_to_string(self.resolve(&format!("C:/{path_on_c}"))?).ok()
+    }
+}
+
+/// Finds League of Legends installs, best candidates first.
+///
+/// Looks at what the Riot Client knows about, then at the usual install folders, both on
+/// Windows and inside Wine, Lutris and Bottles prefixes.
+pub fn candidates() -> Vec<Candidate> {
+    let mut roots = vec![];
+    if cfg!(windows) {
+        roots.push(WindowsRoot { drive_c: None });
+    }
+    roots.extend(wine_prefixes().into_iter().map(|prefix| WindowsRoot {
+        drive_c: Some(prefix.join("drive_c")),
+    }));
+
+    let mut candidates: Vec<Candidate> = roots.iter().flat_map(|r| r.candidates()).collect();
+    // stable, so candidates from the same source keep the order they were found in
+    candidates.sort_by_key(|c| c.source);
+
+    let mut found: Vec<Candidate> = vec![];
+    for candidate in candidates {
+        let is_install =
+            GameSettings::from_path(&Config::game_settings_path(&candidate.path)).is_ok();
+        let key = fs::canonicalize(&candidate.path).ok();
+        let duplicate = found.iter().any(|c| fs::canonicalize(&c.path).ok() == key);
+        if is_install && !duplicate {
+            found.push(candidate);
+        }
+    }
+    found
+}
+
+/// Paths of the installs listed by `RiotClientInstalls.json`, they're the keys of
+/// `associated_client`.
+fn parse_riot_client_installs(content: &str) -> Vec<String> {
+    let Ok(json) = serde_json::from_str::<Value>(content) else {
+        return vec![];
+    };
+    json["associated_client"]
+        .as_object()
+        .map(|clients| {
+            clients
+                .keys()
+                .filter(|path| path.contains("League of Legends"))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads `product_install_full_path` without pulling in a YAML parser, it's a single line.
+fn parse_product_settings(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("product_install_full_path:")?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// Wine prefixes League of Legends is commonly installed into on Linux.
+fn wine_prefixes() -> Vec<PathBuf> {
+    if !cfg!(unix) {
+        return vec![];
+    }
+    let Some(dirs) = directories::BaseDirs::new() else {
+        return vec![];
+    };
+    let home = dirs.home_dir();
+
+    let mut prefixes = vec![home.join(".wine"), home.join("Games/league-of-legends")];
+    for lutris_games in [
+        dirs.config_dir().join("lutris/games"),
+        dirs.data_dir().join("lutris/games"),
+    ] {
+        prefixes.extend(
+            subpaths(&lutris_games)
+                .iter()
+                .filter_map(|game| fs::read_to_string(game).ok())
+                .filter_map(|content| parse_lutris_prefix(&content)),
+        );
+    }
+    for bottles in [
+        dirs.data_dir().join("bottles/bottles"),
+        home.join(".var/app/com.usebottles.bottles/data/bottles/bottles"),
+    ] {
+        prefixes.extend(subpaths(&bottles));
+    }
+
+    prefixes.retain(|prefix| prefix.join("drive_c").is_dir());
+    prefixes
+}
+
+/// Lutris game configs are YAML with the prefix on a `prefix:` line.
+fn parse_lutris_prefix(content: &str) -> Option<PathBuf> {
+    content.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("prefix:")?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| PathBuf::from(value))
+    })
+}
+
+fn subpaths(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_riot_client_files() {
+        let installs = r#"{
+            "associated_client": {
+                "C:/Riot Games/League of Legends/": "C:/Riot Games/Riot Client/RiotClientServices.exe",
+                "C:/Riot Games/VALORANT/live/": "C:/Riot Games/Riot Client/RiotClientServices.exe"
+            },
+            "rc_default": "C:/Riot Games/Riot Client/RiotClientServices.exe"
+        }"#;
+        assert_eq!(
+            parse_riot_client_installs(installs),
+            vec!["C:/Riot Games/League of Legends/"]
+        );
+
+        let settings = "product_install_full_path: \"D:/Games/League of Legends\"\nproduct_install_root: \"D:/Games\"\n";
+        assert_eq!(
+            parse_product_settings(settings).as_deref(),
+            Some("D:/Games/League of Legends")
+        );
+    }
+
+    #[test]
+    fn resolves_paths_inside_a_prefix() {
+        let root = WindowsRoot {
+            drive_c: Some(PathBuf::from("/home/user/.wine/drive_c")),
+        };
+        assert_eq!(
+            root.resolve("C:\\Riot Games\\League of Legends"),
+            Some(PathBuf::from(
+                "/home/user/.wine/drive_c/Riot Games/League of Legends"
+            ))
+        );
+        assert_eq!(root.resolve("D:/Games/League of Legends"), None);
+    }
+}
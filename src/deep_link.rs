@@ -0,0 +1,141 @@
+use std::{env, io, path::Path, process::Command};
+
+use reqwest::Url;
+
+use crate::share_code;
+
+const SCHEME: &str = "lcm";
+
+/// Finds a profile link or share code passed on the command line, either directly or as an
+/// `lcm://import?link=...` / `lcm://import?code=...` deep link.
+pub(crate) fn from_args(args: impl IntoIterator<Item = String>) -> Option<String> {
+    args.into_iter().skip(1).find_map(|arg| {
+        let arg = arg.trim();
+        if arg.starts_with(&format!("{SCHEME}:")) {
+            from_url(arg)
+        } else if arg.starts_with("http") || share_code::is_code(arg) {
+            Some(arg.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if url.host_str() != Some("import") {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "link" || key == "code")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Registers the app as the handler of `lcm://` links, pointing them to the running executable.
+pub(crate) fn register() -> io::Result<()> {
+    let exe = env::current_exe()?;
+    register_for(&exe)
+}
+
+#[cfg(windows)]
+fn register_for(exe: &Path) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let key = format!("HKCU\\Software\\Classes\\{SCHEME}");
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let entries = [
+        vec![key.as_str(), "/ve", "/d", "URL:League Config Manager"],
+        vec![key.as_str(), "/v", "URL Protocol", "/d", ""],
+    ];
+    let command_key = format!("{key}\\shell\\open\\command");
+    let command_entry = vec![command_key.as_str(), "/ve", "/d", command.as_str()];
+
+    // left alone when links already open this executable
+    let current = Command::new("reg")
+        .args(["query", command_key.as_str(), "/ve"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    if String::from_utf8_lossy(&current.stdout).contains(&command) {
+        return Ok(());
+    }
+
+    for entry in entries.iter().chain([&command_entry]) {
+        Command::new("reg")
+            .arg("add")
+            .args(entry)
+            .arg("/f")
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()?;
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn register_for(exe: &Path) -> io::Result<()> {
+    let Some(dirs) = directories::BaseDirs::new() else {
+        return Ok(());
+    };
+    let applications = dirs.data_dir().join("applications");
+    std::fs::create_dir_all(&applications)?;
+
+    let desktop_file = format!("{SCHEME}-league_config_manager.desktop");
+    let desktop_path = applications.join(&desktop_file);
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=League Config Manager\nExec=\"{}\" %u\nNoDisplay=true\nMimeType=x-scheme-handler/{SCHEME};\n",
+        exe.display()
+    );
+    if std::fs::read_to_string(&desktop_path).ok().as_deref() != Some(entry.as_str()) {
+        std::fs::write(&desktop_path, entry)?;
+    }
+
+    // a handler the user picked is kept, the app only becomes the default when there's none
+    let mime_type = format!("x-scheme-handler/{SCHEME}");
+    let current = Command::new("xdg-mime")
+        .args(["query", "default", &mime_type])
+        .output()?;
+    if String::from_utf8_lossy(&current.stdout).trim().is_empty() {
+        Command::new("xdg-mime")
+            .args(["default", &desktop_file, &mime_type])
+            .output()?;
+    }
+    Ok(())
+}
+
+// macOS only reads url schemes from the app bundle's Info.plist
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+fn register_for(_exe: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(arg: &str) -> Vec<String> {
+        vec!["league_config_manager".to_string(), arg.to_string()]
+    }
+
+    #[test]
+    fn reads_deep_links() {
+        assert_eq!(
+            from_args(args("lcm://import?link=https%3A%2F%2Fdpaste.com%2FABC")).as_deref(),
+            Some("https://dpaste.com/ABC")
+        );
+        assert_eq!(
+            from_args(args("lcm://import?code=LCM1.abc")).as_deref(),
+            Some("LCM1.abc")
+        );
+        assert_eq!(from_args(args("lcm://export?code=LCM1.abc")), None);
+    }
+
+    #[test]
+    fn reads_plain_links_and_ignores_other_args() {
+        assert_eq!(
+            from_args(args("https://dpaste.com/ABC")).as_deref(),
+            Some("https://dpaste.com/ABC")
+        );
+        assert_eq!(from_args(args("--verbose")), None);
+    }
+}
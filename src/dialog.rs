@@ -25,6 +25,45 @@ pub(crate) async fn import_zip_path() -> Result<PathBuf, Error> {
     Ok(handle.path().to_owned())
 }
 
+pub(crate) async fn import_folder_path() -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Choose \"Config\" folder to import")
+        .pick_folder()
+        .await
+        .ok_or(Error::DialogClosed)?;
+    Ok(handle.path().to_owned())
+}
+
+pub(crate) async fn import_files_path() -> Result<Vec<PathBuf>, Error> {
+    let handles = rfd::AsyncFileDialog::new()
+        .set_title("Choose game.cfg and PersistedSettings.json to import")
+        .add_filter("settings", &["cfg", "json"])
+        .pick_files()
+        .await
+        .ok_or(Error::DialogClosed)?;
+    Ok(handles.iter().map(|h| h.path().to_owned()).collect())
+}
+
+pub(crate) async fn export_bundle_path() -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Choose folder to export all profiles to")
+        .pick_folder()
+        .await
+        .ok_or(Error::DialogClosed)?;
+    Ok(handle.path().to_owned())
+}
+
+pub(crate) async fn export_qr_path(profile_name: String) -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Save QR code")
+        .add_filter("png", &["png"])
+        .set_file_name(format!("{profile_name}.png"))
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+    Ok(handle.path().to_owned())
+}
+
 pub(crate) async fn export_zip_path(profile: Profile) -> Result<(PathBuf, Profile), Error> {
     let handle = rfd::AsyncFileDialog::new()
         .set_title("Choose folder to export to")
@@ -1,11 +1,144 @@
-#[derive(Debug, Clone, Copy)]
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::paste::ShareError;
+
+#[derive(Debug, Clone)]
 pub enum Error {
     DialogClosed,
     WrongPath,
     MissingPath,
     NameTaken,
-    ZipExport,
+    InvalidName,
     ZipImport,
     ChampionTaken,
-    Import,
+    UnsafeZipEntry,
+    MissingSettingsFile,
+    FileTooLarge,
+    InvalidGameCfg,
+    InvalidPersistedSettings,
+    UnsupportedDrop,
+    InvalidShareCode,
+    UnsupportedShareCode,
+    InvalidSharePayload,
+    UnsupportedSharePayload,
+    NotSharedProfile,
+    QrTooLarge,
+    QrExport,
+    LinkUpload(ShareError),
+    LinkDownload(ShareError),
+    /// The unreadable config file was moved to this backup.
+    ConfigReset(PathBuf),
+    /// The unreadable settings of a profile were moved to this backup.
+    ProfileSettingsReset(String, PathBuf),
+    /// Reading a file or folder failed, `Arc` keeps the error `Clone`.
+    Read(PathBuf, Arc<io::Error>),
+    /// Creating, writing, renaming or removing a file or folder failed.
+    Write(PathBuf, Arc<io::Error>),
+    /// Making a settings file readonly or writable failed.
+    Permissions(PathBuf, Arc<io::Error>),
+    /// A profile folder that couldn't be loaded, it's skipped until it's fixed or removed.
+    BrokenProfile(String, Box<Error>),
+    OpenLog(Arc<io::Error>),
+}
+
+impl Error {
+    /// For `map_err`, e.g. `fs::read(&path).map_err(Error::read(&path))`.
+    pub fn read(path: &Path) -> impl FnOnce(io::Error) -> Error {
+        let path = path.to_path_buf();
+        move |e| Error::Read(path, Arc::new(e))
+    }
+
+    pub fn write(path: &Path) -> impl FnOnce(io::Error) -> Error {
+        let path = path.to_path_buf();
+        move |e| Error::Write(path, Arc::new(e))
+    }
+
+    pub fn permissions(path: &Path) -> impl FnOnce(io::Error) -> Error {
+        let path = path.to_path_buf();
+        move |e| Error::Permissions(path, Arc::new(e))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DialogClosed => write!(f, "Dialog closed without choosing the folder"),
+            Error::WrongPath => write!(
+                f,
+                "Wrong path (folder should look like \".../Riot Games/League of Legends/\""
+            ),
+            Error::MissingPath => write!(
+                f,
+                "Missing path (choose the League of Legends install directory)"
+            ),
+            Error::NameTaken => write!(f, "Name is taken"),
+            Error::InvalidName => write!(f, "Name can't be empty, a dot path or contain slashes"),
+            Error::ZipImport => write!(f, "Error importing profile"),
+            Error::ChampionTaken => write!(f, "Another profile already handles that champion"),
+            Error::UnsafeZipEntry => write!(f, "Zip contains files outside of the profile folder"),
+            Error::MissingSettingsFile => write!(f, "Missing game.cfg or PersistedSettings.json"),
+            Error::FileTooLarge => write!(f, "Settings file is too large"),
+            Error::InvalidGameCfg => write!(f, "game.cfg is not a valid settings file"),
+            Error::InvalidPersistedSettings => {
+                write!(f, "PersistedSettings.json is not a valid settings file")
+            }
+            Error::InvalidShareCode => write!(f, "Share code is incomplete or damaged"),
+            Error::UnsupportedShareCode => {
+                write!(f, "Share code was made by a newer version of the app")
+            }
+            Error::InvalidSharePayload => write!(f, "Shared profile is incomplete or damaged"),
+            Error::UnsupportedSharePayload => {
+                write!(f, "Shared profile was made by a newer version of the app")
+            }
+            Error::NotSharedProfile => write!(
+                f,
+                "Link doesn't lead to a shared profile, it might have expired"
+            ),
+            Error::QrTooLarge => write!(
+                f,
+                "Too much data for a QR code, generate a link and try again"
+            ),
+            Error::QrExport => write!(f, "Couldn't save the QR code"),
+            Error::LinkUpload(error) => write!(f, "Couldn't generate the link, {error}"),
+            Error::LinkDownload(error) => write!(f, "Couldn't download the profile, {error}"),
+            Error::ConfigReset(backup) => write!(
+                f,
+                "Config file couldn't be read, it was kept as {} and reset",
+                backup.display()
+            ),
+            Error::ProfileSettingsReset(profile_name, backup) => write!(
+                f,
+                "Settings of \"{profile_name}\" couldn't be read, they were kept as {} and reset",
+                backup.display()
+            ),
+            Error::UnsupportedDrop => write!(
+                f,
+                "Drop a .zip, a \"Config\" folder or a text file with a profile link"
+            ),
+            Error::Read(path, e) => write!(f, "Couldn't read {}: {e}", path.display()),
+            Error::Write(path, e) => write!(f, "Couldn't write {}: {e}", path.display()),
+            Error::Permissions(path, e) => {
+                write!(f, "Couldn't lock or unlock {}: {e}", path.display())
+            }
+            Error::BrokenProfile(profile_name, e) => {
+                write!(f, "Skipped profile \"{profile_name}\", {e}")
+            }
+            Error::OpenLog(e) => write!(f, "Couldn't open the log file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Read(_, e) | Error::Write(_, e) | Error::Permissions(_, e) => Some(e.as_ref()),
+            Error::OpenLog(e) => Some(e.as_ref()),
+            Error::BrokenProfile(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
 }
@@ -1,25 +1,278 @@
 use ini::Ini;
 
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
 use crate::config::Config;
 
 use crate::error::Error;
 
-use std::fs::File;
-use std::io::Read;
+use crate::share_payload::SharePayload;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
+pub const GAME_FILE: &str = "game.cfg";
+
+pub const SETTINGS_FILE: &str = "PersistedSettings.json";
+
+/// Largest settings file accepted from an import, real ones are a few kilobytes.
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct GameSettings {
     pub game: PathBuf,
     pub settings: PathBuf,
 }
 
+/// Hex crc32 of a settings file, used to notice changed or damaged content.
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:08x}", crc32fast::hash(content))
+}
+
+/// Contents of both settings files, checked to be valid before anything is written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsFiles {
+    pub game: String,
+    pub settings: String,
+}
+
+impl SettingsFiles {
+    pub fn from_bytes(game: Vec<u8>, settings: Vec<u8>) -> Result<Self, Error> {
+        if game.len() as u64 > MAX_FILE_SIZE || settings.len() as u64 > MAX_FILE_SIZE {
+            return Err(Error::FileTooLarge);
+        }
+        let game = String::from_utf8(game).map_err(|_| Error::InvalidGameCfg)?;
+        let settings = String::from_utf8(settings).map_err(|_| Error::InvalidPersistedSettings)?;
+
+        let ini = Ini::load_from_str(Self::strip_bom(&game)).map_err(|_| Error::InvalidGameCfg)?;
+        // every game.cfg key lives in a section like [General] or [HUD]
+        if ini.sections().all(|section| section.is_none()) {
+            return Err(Error::InvalidGameCfg);
+        }
+        if !Self::valid_persisted_settings(Self::strip_bom(&settings)) {
+            return Err(Error::InvalidPersistedSettings);
+        }
+
+        Ok(Self { game, settings })
+    }
+
+    /// PersistedSettings.json holds a `files` list, each with a `name` and its `sections`.
+    fn valid_persisted_settings(settings: &str) -> bool {
+        let Ok(value) = serde_json::from_str::<Value>(settings) else {
+            return false;
+        };
+        value["files"].as_array().is_some_and(|files| {
+            files
+                .iter()
+                .all(|file| file["name"].is_string() && file["sections"].is_array())
+        })
+    }
+
+    /// Reads `game.cfg` and `PersistedSettings.json` from a folder.
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        let game = dir.join(GAME_FILE);
+        let settings = dir.join(SETTINGS_FILE);
+        if !game.is_file() || !settings.is_file() {
+            return Err(Error::MissingSettingsFile);
+        }
+        Self::from_paths(&game, &settings)
+    }
+
+    pub fn from_paths(game: &Path, settings: &Path) -> Result<Self, Error> {
+        Self::from_bytes(Self::read_file(game)?, Self::read_file(settings)?)
+    }
+
+    fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+        let len = fs::metadata(path).map_err(Error::read(path))?.len();
+        if len > MAX_FILE_SIZE {
+            return Err(Error::FileTooLarge);
+        }
+        fs::read(path).map_err(Error::read(path))
+    }
+
+    /// Flattened settings, `Section/Key` for game.cfg and `File/Section/Key` for the other files
+    /// in PersistedSettings.json.
+    pub fn values(&self) -> BTreeMap<String, String> {
+        let mut values = BTreeMap::new();
+
+        if let Ok(ini) = Ini::load_from_str(Self::strip_bom(&self.game)) {
+            for (section, properties) in ini.iter() {
+                let Some(section) = section else {
+                    continue;
+                };
+                for (key, value) in properties.iter() {
+                    values.insert(format!("{section}/{key}"), value.to_string());
+                }
+            }
+        }
+
+        let settings: Value =
+            serde_json::from_str(Self::strip_bom(&self.settings)).unwrap_or_default();
+        for file in settings["files"].as_array().into_iter().flatten() {
+            let Some(file_name) = file["name"].as_str() else {
+                continue;
+            };
+            // same settings as game.cfg
+            if file_name.eq_ignore_ascii_case(GAME_FILE) {
+                continue;
+            }
+            for section in file["sections"].as_array().into_iter().flatten() {
+                let Some(section_name) = section["name"].as_str() else {
+                    continue;
+                };
+                for setting in section["settings"].as_array().into_iter().flatten() {
+                    if let (Some(name), Some(value)) =
+                        (setting["name"].as_str(), setting["value"].as_str())
+                    {
+                        values.insert(
+                            format!("{file_name}/{section_name}/{name}"),
+                            value.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        values
+    }
+
+    /// Copy with `values` set, keys as in [`SettingsFiles::values`]. Settings the files don't
+    /// have yet are added.
+    pub fn with_values(&self, values: &BTreeMap<String, String>) -> Self {
+        let mut ini = Ini::load_from_str(Self::strip_bom(&self.game)).unwrap_or_default();
+        let mut settings: Value =
+            serde_json::from_str(Self::strip_bom(&self.settings)).unwrap_or_default();
+        let mut game_changed = false;
+
+        for (key, value) in values {
+            match key.splitn(3, '/').collect::<Vec<_>>()[..] {
+                [section, name] => {
+                    ini.with_section(Some(section)).set(name, value);
+                    game_changed = true;
+                    // PersistedSettings.json keeps its own copy of game.cfg
+                    Self::set_persisted(&mut settings, GAME_FILE, section, name, value, false);
+                }
+                [file, section, name] => {
+                    Self::set_persisted(&mut settings, file, section, name, value, true)
+                }
+                _ => {}
+            }
+        }
+
+        let mut game = self.game.clone();
+        if game_changed {
+            let mut written = vec![];
+            if ini.write_to(&mut written).is_ok() {
+                game = String::from_utf8(written).unwrap_or(game);
+            }
+        }
+        Self {
+            game,
+            settings: serde_json::to_string_pretty(&settings)
+                .unwrap_or_else(|_| self.settings.clone()),
+        }
+    }
+
+    /// Sets a setting in PersistedSettings.json, adding its file only if `add_file`.
+    fn set_persisted(
+        settings: &mut Value,
+        file: &str,
+        section: &str,
+        name: &str,
+        value: &str,
+        add_file: bool,
+    ) {
+        let Some(files) = settings.get_mut("files").and_then(Value::as_array_mut) else {
+            return;
+        };
+        let is_file = |f: &Value| {
+            f["name"]
+                .as_str()
+                .is_some_and(|n| n.eq_ignore_ascii_case(file))
+        };
+        if add_file && !files.iter().any(is_file) {
+            files.push(json!({ "name": file, "sections": [] }));
+        }
+        let Some(sections) = files
+            .iter_mut()
+            .find(|f| is_file(f))
+            .and_then(|f| f.get_mut("sections"))
+            .and_then(Value::as_array_mut)
+        else {
+            return;
+        };
+
+        if !sections.iter().any(|s| s["name"] == section) {
+            sections.push(json!({ "name": section, "settings": [] }));
+        }
+        let Some(section) = sections.iter_mut().find(|s| s["name"] == section) else {
+            return;
+        };
+        if !section["settings"].is_array() {
+            section["settings"] = json!([]);
+        }
+        let Some(entries) = section["settings"].as_array_mut() else {
+            return;
+        };
+        match entries.iter_mut().find(|s| s["name"] == name) {
+            Some(entry) => entry["value"] = json!(value),
+            None => entries.push(json!({ "name": name, "value": value })),
+        }
+    }
+
+    fn strip_bom(content: &str) -> &str {
+        content.trim_start_matches('\u{feff}')
+    }
+}
+
+/// Suffixes of the files [`GameSettings::write`] keeps next to the settings while writing.
+const NEW_SUFFIX: &str = "lcm-new";
+const BACKUP_SUFFIX: &str = "lcm-old";
+
+fn temp_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn remove_temp_files(paths: &[&Path; 2]) {
+    for path in paths {
+        let _ = fs::remove_file(temp_path(path, NEW_SUFFIX));
+        let _ = fs::remove_file(temp_path(path, BACKUP_SUFFIX));
+    }
+}
+
+fn write_synced(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let write = || {
+        let mut file = File::create(path)?;
+        file.write_all(content)?;
+        file.sync_all()
+    };
+    write().map_err(Error::write(path))
+}
+
+fn is_readonly(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.permissions().readonly())
+}
+
+fn set_readonly_at(path: &Path, readonly: bool) -> Result<(), Error> {
+    let set = || {
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_readonly(readonly);
+        fs::set_permissions(path, permissions)
+    };
+    set().map_err(Error::permissions(path))
+}
+
 impl GameSettings {
     pub fn from_path(folder_path: &Path) -> Result<GameSettings, Error> {
-        let game = folder_path.join("game.cfg");
-        let settings = folder_path.join("PersistedSettings.json");
+        let game = folder_path.join(GAME_FILE);
+        let settings = folder_path.join(SETTINGS_FILE);
         if game.exists() && settings.exists() {
             return Ok(GameSettings { game, settings });
         }
@@ -34,30 +287,99 @@ impl GameSettings {
         Err(Error::MissingPath)
     }
 
-    pub fn from_string(dir: &Path, content: String) -> Result<Self, Error> {
-        let mut parts = content.split(Self::seperator());
-        let game_str = parts.next();
-        let settings_str = parts.next();
+    /// Writes already validated files into `dir`, see [`GameSettings::write`].
+    pub fn from_files(dir: &Path, files: &SettingsFiles) -> Result<Self, Error> {
+        let game_settings = GameSettings {
+            game: dir.join(GAME_FILE),
+            settings: dir.join(SETTINGS_FILE),
+        };
+        game_settings.write(files)?;
+        Ok(game_settings)
+    }
+
+    /// Replaces both files with `files` so they never end up from two different profiles.
+    ///
+    /// The current files are copied next to them and the new ones are synced to temporary
+    /// files before being renamed over them. When that fails halfway the copies are put back,
+    /// after a crash [`GameSettings::recover`] does it. Whether the files are readonly is kept.
+    pub fn write(&self, files: &SettingsFiles) -> Result<(), Error> {
+        let targets = [
+            (self.game.as_path(), files.game.as_bytes()),
+            (self.settings.as_path(), files.settings.as_bytes()),
+        ];
+        let paths = [self.game.as_path(), self.settings.as_path()];
+        let readonly = is_readonly(&self.game);
 
-        if game_str.is_none() || settings_str.is_none() {
-            return Err(Error::Import);
+        let result = Self::replace(&targets, readonly);
+        match &result {
+            Ok(()) => log::info!(file:% = self.game.display(); "wrote settings files"),
+            Err(e) => {
+                log::warn!(file:% = self.game.display(), error:% = e; "write failed, restoring");
+                // if this fails too the copies are still there for `recover`
+                Self::restore_backups(&paths, readonly)?;
+            }
         }
-        let game_str = game_str.unwrap();
-        let settings_str = settings_str.unwrap();
+        remove_temp_files(&paths);
+        result
+    }
 
-        let game = dir.join("game.cfg");
-        let settings = dir.join("PersistedSettings.json");
+    fn replace(targets: &[(&Path, &[u8]); 2], readonly: bool) -> Result<(), Error> {
+        for (path, _) in targets {
+            if path.exists() {
+                let current = fs::read(path).map_err(Error::read(path))?;
+                // only complete copies get the backup name, `recover` trusts every one it finds
+                let partial = temp_path(path, NEW_SUFFIX);
+                write_synced(&partial, &current)?;
+                let backup = temp_path(path, BACKUP_SUFFIX);
+                fs::rename(&partial, &backup).map_err(Error::write(&backup))?;
+            }
+        }
+        for (path, content) in targets {
+            write_synced(&temp_path(path, NEW_SUFFIX), content)?;
+        }
+        for (path, _) in targets {
+            if path.exists() {
+                set_readonly_at(path, false)?;
+            }
+            fs::rename(temp_path(path, NEW_SUFFIX), path).map_err(Error::write(path))?;
+        }
+        for (path, _) in targets {
+            set_readonly_at(path, readonly)?;
+        }
+        Ok(())
+    }
 
-        let mut game_file = File::create(game).expect("Unable to open file");
-        game_file
-            .write_all(game_str.as_bytes())
-            .expect("Unable to write data");
+    fn restore_backups(paths: &[&Path; 2], readonly: bool) -> Result<(), Error> {
+        for path in paths {
+            let backup = temp_path(path, BACKUP_SUFFIX);
+            if backup.exists() {
+                if path.exists() {
+                    set_readonly_at(path, false)?;
+                }
+                fs::rename(backup, path).map_err(Error::write(path))?;
+            }
+        }
+        for path in paths.iter().filter(|path| path.exists()) {
+            set_readonly_at(path, readonly)?;
+        }
+        Ok(())
+    }
 
-        let mut settings_file = File::create(settings).expect("Unable to open file");
-        settings_file
-            .write_all(settings_str.as_bytes())
-            .expect("Unable to write data");
-        Self::from_path(dir)
+    /// Undoes a [`GameSettings::write`] into `dir` that was cut off. Both copies of the old
+    /// files being there means the new ones might be halfway renamed, so the old ones are put
+    /// back.
+    pub fn recover(dir: &Path) {
+        let game = dir.join(GAME_FILE);
+        let settings = dir.join(SETTINGS_FILE);
+        let paths = [game.as_path(), settings.as_path()];
+        if paths
+            .iter()
+            .all(|path| temp_path(path, BACKUP_SUFFIX).exists())
+        {
+            let result = Self::restore_backups(&paths, is_readonly(&game));
+            log::warn!(dir:% = dir.display(), ok = result.is_ok(); "recovered interrupted write");
+        }
+        remove_temp_files(&paths);
     }
 
     pub fn update_paths(&mut self, dir: &Path) {
@@ -65,59 +387,122 @@ impl GameSettings {
         self.settings = dir.join("PersistedSettings.json");
     }
 
+    /// Missing files count as writable.
     pub fn readonly(&self) -> bool {
-        let f = File::open(&self.game).expect("File should always exist");
-        f.metadata().unwrap().permissions().readonly()
+        is_readonly(&self.game)
     }
 
-    fn set_readonly_path(p: &PathBuf, value: bool) {
-        let f = File::open(p).expect("File should always exist");
-        let mut perms = f.metadata().expect("Error reading meatadata").permissions();
-        perms.set_readonly(value);
-        std::fs::set_permissions(p, perms).unwrap();
+    pub fn set_readonly(&self, value: bool) -> Result<(), Error> {
+        set_readonly_at(&self.game, value)?;
+        set_readonly_at(&self.settings, value)
     }
 
-    pub fn set_readonly(&self, value: bool) {
-        Self::set_readonly_path(&self.game, value);
-        Self::set_readonly_path(&self.settings, value);
+    /// Hash of both files, used to notice a profile changed since its link was generated.
+    pub fn files_hash(&self) -> String {
+        let mut content = fs::read(&self.game).unwrap_or_default();
+        content.extend(fs::read(&self.settings).unwrap_or_default());
+        content_hash(&content)
     }
 
-    fn seperator() -> &'static str {
-        "<lcm-seperator>"
-    }
-
-    pub fn to_paste_string(&self) -> String {
-        let mut f = File::open(&self.game).expect("File should always exist");
-        let mut res: String = "".to_string();
-        f.read_to_string(&mut res).expect("Should always be valid");
-        res.push_str(Self::seperator());
-        let mut f = File::open(&self.settings).expect("File should always exist");
-        f.read_to_string(&mut res).expect("Should always be valid");
+    /// Content shared through links and share codes.
+    pub fn to_paste_string(&self, profile_name: &str) -> Result<String, Error> {
+        let game = fs::read_to_string(&self.game).map_err(Error::read(&self.game))?;
+        let settings = fs::read_to_string(&self.settings).map_err(Error::read(&self.settings))?;
 
-        res
+        Ok(SharePayload::new(profile_name, game, settings).encode())
     }
 
-    fn load_ini(&self) -> Result<Ini, ini::Error> {
-        Ini::load_from_file(&self.game)
+    fn load_ini(&self) -> Result<Ini, Error> {
+        Ini::load_from_file(&self.game).map_err(|e| match e {
+            ini::Error::Io(e) => Error::read(&self.game)(e),
+            ini::Error::Parse(_) => Error::InvalidGameCfg,
+        })
     }
 
-    fn save_ini(&self, ini: Ini) -> Result<(), ini::Error> {
-        ini.write_to_file(&self.game)?;
-        Ok(())
+    fn save_ini(&self, ini: Ini) -> Result<(), Error> {
+        ini.write_to_file(&self.game)
+            .map_err(Error::write(&self.game))
     }
 
-    pub fn reset_resolution(&self) -> Result<(), ini::Error> {
+    pub fn reset_resolution(&self) -> Result<(), Error> {
         let readonly = self.readonly();
-        self.set_readonly(false);
         let mut ini = self.load_ini()?;
 
         ini.delete_from(Some("General"), "Width");
         ini.delete_from(Some("General"), "Height");
 
-        self.save_ini(ini)?;
+        self.set_readonly(false)?;
+        let saved = self.save_ini(ini);
+        self.set_readonly(readonly)?;
+        saved
+    }
+}
 
-        self.set_readonly(readonly);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(())
+    use crate::test_util::{temp_dir, GAME, SETTINGS};
+
+    #[test]
+    fn writes_replace_both_files_and_keep_readonly() {
+        let dir = temp_dir("write");
+        let files = SettingsFiles::from_bytes(GAME.into(), SETTINGS.into()).unwrap();
+        let cfg = GameSettings::from_files(&dir, &files).unwrap();
+        cfg.set_readonly(true).unwrap();
+
+        let changed = files.with_values(&BTreeMap::from([(
+            "General/Width".to_string(),
+            "800".to_string(),
+        )]));
+        cfg.write(&changed).unwrap();
+        assert!(fs::read_to_string(&cfg.game).unwrap().contains("Width=800"));
+        assert!(cfg.readonly());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        cfg.set_readonly(false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recovery_puts_back_both_old_files() {
+        let dir = temp_dir("recover");
+        let cfg = GameSettings::from_files(
+            &dir,
+            &SettingsFiles::from_bytes(GAME.into(), SETTINGS.into()).unwrap(),
+        )
+        .unwrap();
+        // cut off after renaming the new game.cfg
+        fs::write(temp_path(&cfg.game, BACKUP_SUFFIX), GAME).unwrap();
+        fs::write(temp_path(&cfg.settings, BACKUP_SUFFIX), SETTINGS).unwrap();
+        fs::write(&cfg.game, "[General]\nWidth=800\n").unwrap();
+        fs::write(temp_path(&cfg.settings, NEW_SUFFIX), "{}").unwrap();
+
+        GameSettings::recover(&dir);
+        assert_eq!(fs::read_to_string(&cfg.game).unwrap(), GAME);
+        assert_eq!(fs::read_to_string(&cfg.settings).unwrap(), SETTINGS);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recovery_ignores_partial_backups() {
+        let dir = temp_dir("recover-partial");
+        let cfg = GameSettings::from_files(
+            &dir,
+            &SettingsFiles::from_bytes(GAME.into(), SETTINGS.into()).unwrap(),
+        )
+        .unwrap();
+        // cut off while copying PersistedSettings.json
+        fs::write(temp_path(&cfg.game, BACKUP_SUFFIX), GAME).unwrap();
+        fs::write(temp_path(&cfg.settings, NEW_SUFFIX), "{\"fil").unwrap();
+
+        GameSettings::recover(&dir);
+        assert_eq!(fs::read_to_string(&cfg.game).unwrap(), GAME);
+        assert_eq!(fs::read_to_string(&cfg.settings).unwrap(), SETTINGS);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,112 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+use iced::{
+    futures::{SinkExt, Stream},
+    stream,
+};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use crate::{config::Config, share_payload::MAX_PAYLOAD_SIZE};
+
+/// Port the running instance listens on, one per data folder.
+const PORT_FILE: &str = "instance.port";
+
+/// Sent first by a starting instance and answered by the running one, anyone else connecting
+/// is ignored.
+const HELLO: &str = "lcm";
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+fn port_path() -> PathBuf {
+    Config::get_config_dir().join(PORT_FILE)
+}
+
+/// Hands `link` to an instance already running on the same data folder, `true` when it took
+/// it and this one should exit instead of also enforcing locks and writing the config.
+pub(crate) fn forward(link: Option<&str>) -> bool {
+    let send = || -> io::Result<bool> {
+        let port = fs::read_to_string(port_path())?
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        write!(stream, "{HELLO}\n{}\n", link.unwrap_or_default())?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply.trim_end() == HELLO)
+    };
+    send().unwrap_or(false)
+}
+
+/// Links forwarded by instances started later, `None` for one started without a link.
+pub(crate) fn listen() -> impl Stream<Item = Option<String>> {
+    stream::channel(10, |mut output| async move {
+        let Ok(listener) = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await else {
+            return;
+        };
+        let Ok(address) = listener.local_addr() else {
+            return;
+        };
+        if fs::write(port_path(), address.port().to_string()).is_err() {
+            return;
+        }
+        while let Ok((socket, _)) = listener.accept().await {
+            if let Ok(Some(link)) = tokio::time::timeout(TIMEOUT, receive(socket)).await {
+                let _ = output.send(link).await;
+            }
+        }
+    })
+}
+
+async fn receive(socket: tokio::net::TcpStream) -> Option<Option<String>> {
+    let (reader, mut writer) = socket.into_split();
+    let mut lines = tokio::io::BufReader::new(reader.take(MAX_PAYLOAD_SIZE)).lines();
+    if lines.next_line().await.ok()?? != HELLO {
+        return None;
+    }
+    let link = lines.next_line().await.ok()?.unwrap_or_default();
+    writer
+        .write_all(format!("{HELLO}\n").as_bytes())
+        .await
+        .ok()?;
+    Some(Some(link).filter(|link| !link.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the running instance makes of `request`, and what it answered.
+    async fn exchange(request: &str) -> (Option<Option<String>>, String) {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let mut client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        let received = receive(socket).await;
+        let mut reply = String::new();
+        let _ = client.read_to_string(&mut reply).await;
+        (received, reply)
+    }
+
+    #[tokio::test]
+    async fn takes_links_only_after_the_hello() {
+        let (received, reply) = exchange("lcm\nhttps://dpaste.com/ABC\n").await;
+        assert_eq!(received, Some(Some("https://dpaste.com/ABC".to_string())));
+        assert_eq!(reply, "lcm\n");
+
+        assert_eq!(exchange("lcm\n\n").await.0, Some(None));
+        assert_eq!(exchange("GET / HTTP/1.1\n").await, (None, String::new()));
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::BTreeMap, fs, time::Duration};
+
+use crate::{
+    error::Error,
+    game_settings::{GameSettings, SettingsFiles},
+};
+
+/// How often the live settings are compared with the locked ones.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Files changed more recently than this might still be being written.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// What's kept while locking is enforced.
+#[derive(Debug)]
+enum Locked {
+    /// Both files, the whole settings are locked.
+    Files(SettingsFiles),
+    /// Only these settings, keys as in [`SettingsFiles::values`], the game keeps the rest.
+    Keys(BTreeMap<String, String>),
+}
+
+/// Locked settings, written back whenever something else changes the live files, e.g. the
+/// client rewriting them after a game.
+#[derive(Debug)]
+pub(crate) struct LockGuard {
+    locked: Locked,
+    /// Live files the last time they were checked, `None` checks them on the next tick.
+    hash: Option<String>,
+}
+
+impl LockGuard {
+    /// Locks the current content of the live settings.
+    pub fn new(cfg: &GameSettings) -> Result<Self, Error> {
+        let dir = cfg.game.parent().ok_or(Error::WrongPath)?;
+        Ok(Self {
+            locked: Locked::Files(SettingsFiles::from_dir(dir)?),
+            hash: Some(cfg.files_hash()),
+        })
+    }
+
+    /// Locks `files` saved while the lock was on earlier, they're checked right away so changes
+    /// made while the app was closed are put back.
+    pub fn saved(files: SettingsFiles) -> Self {
+        Self {
+            locked: Locked::Files(files),
+            hash: None,
+        }
+    }
+
+    /// Content of both locked files, `None` when only some keys are locked.
+    pub fn files(&self) -> Option<&SettingsFiles> {
+        match &self.locked {
+            Locked::Files(files) => Some(files),
+            Locked::Keys(_) => None,
+        }
+    }
+
+    /// Locks only `keys` to their values, they're checked right away so they also end up on
+    /// top of a profile that was just used.
+    pub fn keys(keys: BTreeMap<String, String>) -> Self {
+        Self {
+            locked: Locked::Keys(keys),
+            hash: None,
+        }
+    }
+
+    /// Puts the locked settings back if the live files changed and aren't being written
+    /// anymore, returns whether they were restored.
+    pub fn enforce(&mut self, cfg: &GameSettings) -> Result<bool, Error> {
+        let hash = cfg.files_hash();
+        if self.hash.as_ref() == Some(&hash) || Self::recently_modified(cfg) {
+            return Ok(false);
+        }
+        let dir = cfg.game.parent().ok_or(Error::WrongPath)?;
+        let files = match &self.locked {
+            Locked::Files(files) => {
+                if SettingsFiles::from_dir(dir).ok().as_ref() == Some(files) {
+                    self.hash = Some(hash);
+                    return Ok(false);
+                }
+                files.clone()
+            }
+            Locked::Keys(keys) => {
+                let live = SettingsFiles::from_dir(dir)?;
+                let values = live.values();
+                if keys
+                    .iter()
+                    .all(|(key, value)| values.get(key) == Some(value))
+                {
+                    self.hash = Some(hash);
+                    return Ok(false);
+                }
+                live.with_values(keys)
+            }
+        };
+
+        cfg.write(&files)?;
+        if let Locked::Files(_) = self.locked {
+            // the client might have put writable files in place of the locked ones
+            cfg.set_readonly(true)?;
+        }
+        self.hash = Some(cfg.files_hash());
+        Ok(true)
+    }
+
+    fn recently_modified(cfg: &GameSettings) -> bool {
+        [&cfg.game, &cfg.settings].iter().any(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|elapsed| elapsed < SETTLE_TIME)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::SystemTime;
+
+    use crate::test_util::{temp_dir, GAME, SETTINGS};
+
+    fn set_modified(cfg: &GameSettings, time: SystemTime) {
+        for path in [&cfg.game, &cfg.settings] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn restores_changed_settings() {
+        let dir = temp_dir("lock");
+        let files = SettingsFiles::from_bytes(GAME.into(), SETTINGS.into()).unwrap();
+        let cfg = GameSettings::from_files(&dir, &files).unwrap();
+        let mut guard = LockGuard::new(&cfg).unwrap();
+        assert!(!guard.enforce(&cfg).unwrap());
+
+        fs::write(&cfg.game, "[General]\nWidth=800\n").unwrap();
+        // still being written
+        assert!(!guard.enforce(&cfg).unwrap());
+
+        set_modified(&cfg, SystemTime::now() - Duration::from_secs(60));
+        assert!(guard.enforce(&cfg).unwrap());
+        assert_eq!(fs::read_to_string(&cfg.game).unwrap(), GAME);
+        assert!(cfg.readonly());
+
+        cfg.set_readonly(false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saved_locks_put_back_changes_made_while_closed() {
+        let dir = temp_dir("lock-saved");
+        let files = SettingsFiles::from_bytes(GAME.into(), SETTINGS.into()).unwrap();
+        let cfg = GameSettings::from_files(&dir, &files).unwrap();
+        set_modified(&cfg, SystemTime::now() - Duration::from_secs(60));
+
+        let mut guard = LockGuard::saved(files.clone());
+        assert!(!guard.enforce(&cfg).unwrap());
+        assert!(!cfg.readonly());
+
+        fs::write(&cfg.game, "[General]\nWidth=800\n").unwrap();
+        set_modified(&cfg, SystemTime::now() - Duration::from_secs(60));
+        let mut guard = LockGuard::saved(files);
+        assert!(guard.enforce(&cfg).unwrap());
+        assert_eq!(fs::read_to_string(&cfg.game).unwrap(), GAME);
+
+        cfg.set_readonly(false).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn locked_keys_end_up_in_both_files() {
+        let game = "[General]\nWidth=1920\n[HUD]\nCameraLockMode=0\n";
+        let settings = r#"{"files":[
+            {"name":"game.cfg","sections":[{"name":"HUD","settings":[{"name":"CameraLockMode","value":"0"}]}]},
+            {"name":"Input.ini","sections":[{"name":"GameEvents","settings":[{"name":"evtCastSpell1","value":"[q]"}]}]}
+        ]}"#;
+        let files = SettingsFiles::from_bytes(game.into(), settings.into()).unwrap();
+        let keys = BTreeMap::from([
+            ("HUD/CameraLockMode".to_string(), "2".to_string()),
+            (
+                "Input.ini/GameEvents/evtCastSpell1".to_string(),
+                "[a]".to_string(),
+            ),
+            (
+                "Input.ini/GameEvents/evtCastSpell2".to_string(),
+                "[s]".to_string(),
+            ),
+        ]);
+
+        let locked = files.with_values(&keys);
+        let values = locked.values();
+        for (key, value) in &keys {
+            assert_eq!(values.get(key), Some(value));
+        }
+        assert_eq!(values["General/Width"], "1920");
+        assert!(locked.settings.contains(r#""value": "2""#));
+    }
+}
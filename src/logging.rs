@@ -0,0 +1,226 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use log::{kv, Level, LevelFilter, Log, Metadata, Record};
+
+use crate::{clock, config::Config, error::Error};
+
+pub const LOG_FILE: &str = "lcm.log";
+
+/// The log is rotated once it grows past this.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Rotated logs kept next to the current one, `lcm.log.1` is the newest.
+const KEPT_LOGS: usize = 3;
+
+/// Writes one line per record to the log in the data folder, rotating it when it gets too big.
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+/// Starts logging to the data folder, also logs panics since stderr is hidden on Windows.
+pub fn init() {
+    let path = log_path();
+    let logger = FileLogger {
+        file: Mutex::new(open(&path)),
+        path,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{info}");
+        default_hook(info);
+    }));
+}
+
+pub fn log_path() -> PathBuf {
+    Config::get_config_dir().join(LOG_FILE)
+}
+
+/// Opens the log with the default app for text files.
+pub fn open_log() -> Result<(), Error> {
+    let path = log_path();
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = file.flush();
+    }
+    open_command(&path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| Error::OpenLog(Arc::new(e)))
+}
+
+#[cfg(windows)]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(path);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(path);
+    command
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
+
+/// Last `count` lines of the current log, for diagnostics.
+pub fn tail(count: usize) -> String {
+    let log = fs::read_to_string(log_path()).unwrap_or_default();
+    let lines: Vec<_> = log.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+fn open(path: &Path) -> Option<File> {
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+/// Shifts `lcm.log` to `lcm.log.1`, `lcm.log.1` to `lcm.log.2` and so on, dropping the oldest.
+fn rotate(path: &Path) -> io::Result<()> {
+    let rotated = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    };
+    let _ = fs::remove_file(rotated(KEPT_LOGS));
+    for n in (1..KEPT_LOGS).rev() {
+        let from = rotated(n);
+        if from.exists() {
+            fs::rename(from, rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+/// `2024-10-19T14:03:09Z INFO  target: message key=value ...`, one record per line.
+fn format_line(secs: u64, record: &Record) -> String {
+    let mut line = format!(
+        "{} {:<5} {}: {}",
+        clock::timestamp_from_unix(secs),
+        record.level(),
+        record.target(),
+        record.args().to_string().replace('\n', " | "),
+    );
+    let mut fields = Fields(&mut line);
+    let _ = record.key_values().visit(&mut fields);
+    line.push('\n');
+    line
+}
+
+/// Appends key-value pairs as ` key=value`, quoting values with spaces.
+struct Fields<'a>(&'a mut String);
+
+impl<'kvs> kv::VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = value.to_string();
+        if value.is_empty() || value.contains([' ', '"', '=', '\n']) {
+            let _ = write!(self.0, " {key}={value:?}");
+        } else {
+            let _ = write!(self.0, " {key}={value}");
+        }
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // dependencies only get their warnings and errors logged
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME")) || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(clock::now(), record);
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        let full = file
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .is_some_and(|m| m.len() > MAX_SIZE);
+        if full {
+            // the file has to be closed before it can be renamed on Windows
+            *file = None;
+            let _ = rotate(&self.path);
+            *file = open(&self.path);
+        }
+        if let Some(file) = file.as_mut() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn lines_have_time_level_and_fields() {
+        let fields = [("profile", "mid lane"), ("champion", "Ahri")];
+        let line = format_line(
+            1_729_346_589,
+            &Record::builder()
+                .args(format_args!("applied profile"))
+                .level(Level::Info)
+                .target("league_config_manager::app")
+                .key_values(&fields)
+                .build(),
+        );
+        assert_eq!(
+            line,
+            "2024-10-19T14:03:09Z INFO  league_config_manager::app: applied profile \
+             profile=\"mid lane\" champion=Ahri\n"
+        );
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_logs() {
+        let dir = temp_dir("log");
+        let path = dir.join(LOG_FILE);
+
+        for n in 0..=KEPT_LOGS + 1 {
+            fs::write(&path, n.to_string()).unwrap();
+            rotate(&path).unwrap();
+        }
+
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join(format!("{LOG_FILE}.1"))).unwrap(),
+            (KEPT_LOGS + 1).to_string()
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join(format!("{LOG_FILE}.{KEPT_LOGS}"))).unwrap(),
+            2.to_string()
+        );
+        assert!(!dir.join(format!("{LOG_FILE}.{}", KEPT_LOGS + 1)).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,27 +2,55 @@
 
 mod app;
 mod champion;
+mod clock;
 mod colors;
 mod config;
+mod deep_link;
 mod dialog;
 mod error;
 mod game_settings;
+mod instance;
+mod lock;
+mod logging;
 mod message;
 mod paste;
+mod preview;
 mod profile;
+mod qr;
+mod share_code;
+mod share_payload;
+#[cfg(test)]
+mod test_util;
 mod theme;
+mod versioned;
 mod websocket;
 mod widget;
 
-use std::sync::Arc;
-
 use crate::theme::Theme;
 
 use app::App;
+use config::Config;
 use iced::{advanced::graphics::image::image_rs::ImageFormat, window, Size};
-use tokio::sync::Mutex;
 
 fn main() -> Result<(), iced::Error> {
+    Config::init_data_dir(std::env::args());
+    logging::init();
+    log::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        data_dir:% = Config::get_config_dir().display();
+        "started"
+    );
+    let link = deep_link::from_args(std::env::args());
+    if instance::forward(link.as_deref()) {
+        log::info!("handed over to the running instance");
+        return Ok(());
+    }
+    if !Config::is_portable() && !Config::has_data_dir_override() {
+        std::thread::spawn(|| {
+            let _ = deep_link::register();
+        });
+    }
+
     iced::application("League Config Manager", App::update, App::view)
         .theme(|_| Theme)
         .font(include_bytes!("../fonts/icons.ttf").as_slice())
@@ -40,5 +68,5 @@ fn main() -> Result<(), iced::Error> {
             ..window::Settings::default()
         })
         .subscription(App::subscription)
-        .run_with(App::new)
+        .run_with(move || App::new(link))
 }
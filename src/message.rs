@@ -1,31 +1,60 @@
-use crate::{error::Error, profile::Profile, websocket};
+use crate::{
+    app::RequestKind, config::Candidate, error::Error, paste::ShareError, profile::Profile,
+    websocket,
+};
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Debug, Clone)]
 pub(crate) enum Message {
     FindLocation,
     SetLocation(Result<PathBuf, Error>),
+    InstallsDetected(Vec<Candidate>),
+    RemoveInstall(PathBuf),
     SetReadonly(bool),
+    SetEnforceLock(bool),
+    EnforceLock,
+    ShowLockedKeys,
+    CloseLockedKeys,
+    FilterKeys(String),
+    LockKey(String, bool),
+    CheckDrift,
+    SaveDrift,
+    DiscardDrift,
 
     //Profile
     AddProfile,
     RemoveProfile(String),
     UseProfile(Profile),
+    UseProfileEverywhere(Profile),
     Edit(String),
     Confirm(String),
     Reset(String),
     OnChange(String, String),
     Export(Profile),
     SetExport(Result<(PathBuf, Profile), Error>),
+    ExportAll,
+    SetExportAll(Result<PathBuf, Error>),
     Import,
     SetImport(Result<PathBuf, Error>),
+    ImportFolder,
+    SetImportFolder(Result<PathBuf, Error>),
+    ImportFiles,
+    SetImportFiles(Result<Vec<PathBuf>, Error>),
     PickListChange(String, &'static str),
 
     CopyLink(String),
-    GenerateLink(String, String),
+    CopyCode(String),
+    ShowQr(String),
+    CloseQr,
+    SaveQr,
+    SetSaveQr(Result<PathBuf, Error>),
+    GenerateLink(String),
     PostLink(String, String),
-    PostLinkError(String),
+    PostLinkError(ShareError),
+    ShareRetrying(RequestKind, u32, Duration, ShareError),
+    CancelRequest(RequestKind),
+    RequestFinished(RequestKind),
 
     ChangeLink(String),
 
@@ -34,4 +63,15 @@ pub(crate) enum Message {
     FetchError(Error),
 
     WebsocketEvent(websocket::Event),
+
+    FileDropped(PathBuf),
+
+    AcceptImport,
+    RejectImport,
+
+    OpenLog,
+    CopyDiagnostics,
+
+    /// Another instance was started on the same data folder, with the link it was given.
+    OtherInstance(Option<String>),
 }
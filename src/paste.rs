@@ -1,70 +1,682 @@
-use std::sync::Arc;
+use std::{fmt, time::Duration};
 
-use tokio::{runtime, sync::Mutex};
+use iced::{
+    futures::{future::BoxFuture, SinkExt, Stream},
+    stream,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-pub(crate) async fn post(
-    client: Arc<Mutex<reqwest::Client>>,
-    content: String,
-) -> Result<String, reqwest::Error> {
-    let rt = runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
+use crate::share_payload::MAX_PAYLOAD_SIZE;
+
+const USER_AGENT: &str = "League Config Manager - Settings manager for League of Legends";
+
+/// Paste services answer in well under a second, a request taking this long is abandoned.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tries per request, only failures that might go away on their own are retried.
+pub(crate) const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled before every next one.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const DPASTE_URL: &str = "https://dpaste.com";
+
+/// dpaste deletes pastes after this many days, asked for explicitly so the expiry is known.
+const DPASTE_EXPIRY_DAYS: u64 = 7;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+const GIST_FILE: &str = "league_config_manager_profile.txt";
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ShareError {
+    Timeout,
+    Connection,
+    /// The service answered with 429 Too Many Requests.
+    RateLimited,
+    /// Any other unsuccessful status code.
+    Status(u16),
+    Request(String),
+    MalformedResponse,
+    /// The download is bigger than any shared profile can be.
+    TooLarge,
+}
 
-    let params = [("content", content)];
+impl ShareError {
+    /// Whether the same request might succeed if it's sent again a bit later.
+    fn is_transient(&self) -> bool {
+        match self {
+            ShareError::Timeout | ShareError::Connection | ShareError::RateLimited => true,
+            ShareError::Status(status) => *status >= 500,
+            ShareError::Request(_) | ShareError::MalformedResponse | ShareError::TooLarge => false,
+        }
+    }
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::Timeout => write!(f, "the paste service took too long to answer"),
+            ShareError::Connection => write!(f, "couldn't connect to the paste service"),
+            ShareError::RateLimited => {
+                write!(f, "the paste service is limiting requests, try again later")
+            }
+            ShareError::Status(status) => {
+                write!(f, "the paste service answered with status {status}")
+            }
+            ShareError::Request(e) => e.fmt(f),
+            ShareError::MalformedResponse => {
+                write!(f, "unexpected response from the paste service")
+            }
+            ShareError::TooLarge => write!(f, "the link leads to something too large"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ShareError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ShareError::Timeout
+        } else if e.is_connect() {
+            ShareError::Connection
+        } else if let Some(status) = e.status() {
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                ShareError::RateLimited
+            } else {
+                ShareError::Status(status.as_u16())
+            }
+        } else {
+            ShareError::Request(e.to_string())
+        }
+    }
+}
+
+/// Reported while a request to the paste service runs.
+#[derive(Debug, Clone)]
+pub(crate) enum Progress {
+    /// Try number `attempt` failed with `error`, the next one starts after `delay`.
+    Retrying {
+        attempt: u32,
+        delay: Duration,
+        error: ShareError,
+    },
+    Finished(Result<String, ShareError>),
+}
 
-    let res = rt
-        .spawn(async move {
-            let client = client.lock().await;
+/// A paste service that profile links are uploaded to and downloaded from.
+pub(crate) trait ShareBackend: Send + Sync {
+    /// Uploads `content` and returns the link to share.
+    fn post(
+        &self,
+        client: reqwest::Client,
+        content: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>>;
+
+    /// Downloads the content behind a link created by [`ShareBackend::post`].
+    fn get(
+        &self,
+        client: reqwest::Client,
+        link: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>>;
+
+    /// Whether `link` points to this service.
+    fn handles(&self, link: &str) -> bool;
+
+    /// Days until links created by [`ShareBackend::post`] expire, `None` when they're kept.
+    fn expiry_days(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Paste service used for sharing, selected with the `share` field of the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShareService {
+    #[default]
+    Dpaste,
+    /// Self-hosted hastebin compatible server, e.g. `http://localhost:7777`.
+    Hastebin { base_url: String },
+    /// GitHub Gist or a server with the same API, creating gists needs a token.
+    Gist {
+        #[serde(default = "ShareService::github_api_url")]
+        api_url: String,
+        #[serde(default)]
+        token: String,
+    },
+}
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+impl ShareService {
+    fn github_api_url() -> String {
+        GITHUB_API_URL.to_string()
+    }
 
-            client
-                .post("https://dpaste.com/api/")
-                .header(
-                    "User-Agent",
-                    "League Config Manager - Settings manager for League of Legends",
-                )
+    pub fn backend(&self) -> Box<dyn ShareBackend> {
+        match self {
+            ShareService::Dpaste => Box::new(Dpaste::new(DPASTE_URL)),
+            ShareService::Hastebin { base_url } => Box::new(Hastebin::new(base_url)),
+            ShareService::Gist { api_url, token } => Box::new(Gist::new(api_url, token)),
+        }
+    }
+
+    /// Backend able to download `link`, links from the built-in services work whichever one
+    /// is configured.
+    pub fn backend_for(&self, link: &str) -> Box<dyn ShareBackend> {
+        let configured = self.backend();
+        if configured.handles(link) {
+            return configured;
+        }
+        let known: [Box<dyn ShareBackend>; 2] = [
+            Box::new(Dpaste::new(DPASTE_URL)),
+            Box::new(Gist::new(GITHUB_API_URL, "")),
+        ];
+        known
+            .into_iter()
+            .find(|b| b.handles(link))
+            .unwrap_or(configured)
+    }
+}
+
+/// Body of `res` as text, refused as soon as it's bigger than `limit` instead of buffering it
+/// all.
+async fn read_text(mut res: reqwest::Response, limit: u64) -> Result<String, ShareError> {
+    if res.content_length().is_some_and(|len| len > limit) {
+        return Err(ShareError::TooLarge);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(ShareError::TooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    String::from_utf8(body).map_err(|_| ShareError::MalformedResponse)
+}
+
+/// Service and its address for diagnostics, the Gist token is left out.
+impl fmt::Display for ShareService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareService::Dpaste => write!(f, "dpaste"),
+            ShareService::Hastebin { base_url } => write!(f, "hastebin at {base_url}"),
+            ShareService::Gist { api_url, token } => {
+                let auth = if token.is_empty() {
+                    "no token"
+                } else {
+                    "token set"
+                };
+                write!(f, "gist at {api_url} ({auth})")
+            }
+        }
+    }
+}
+
+/// Id of the paste in `link`, the last part of its path.
+fn last_segment(link: &str) -> &str {
+    link.split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Whether `link` is on the host of `base_url`, a plain prefix check would also match e.g.
+/// `https://dpaste.com.example.org`.
+fn same_host(link: &str, base_url: &str) -> bool {
+    match (reqwest::Url::parse(link), reqwest::Url::parse(base_url)) {
+        (Ok(link), Ok(base)) => link.host_str() == base.host_str() && link.port() == base.port(),
+        _ => false,
+    }
+}
+
+pub(crate) struct Dpaste {
+    base_url: String,
+}
+
+impl Dpaste {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl ShareBackend for Dpaste {
+    fn post(
+        &self,
+        client: reqwest::Client,
+        content: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let url = format!("{}/api/", self.base_url);
+        Box::pin(async move {
+            let params = [
+                ("content", content),
+                ("expiry_days", DPASTE_EXPIRY_DAYS.to_string()),
+            ];
+            let res = client
+                .post(url)
+                .header("User-Agent", USER_AGENT)
                 .form(&params)
                 .send()
-                .await
+                .await?;
+
+            res.error_for_status_ref()?;
+            let link = res
+                .headers()
+                .get("location")
+                .and_then(|l| l.to_str().ok())
+                .ok_or(ShareError::MalformedResponse)?;
+            Ok(link.to_string())
+        })
+    }
+
+    fn get(
+        &self,
+        client: reqwest::Client,
+        link: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let id = last_segment(&link).trim_end_matches(".txt");
+        let url = format!("{}/{id}.txt", self.base_url);
+        Box::pin(async move {
+            let res = client
+                .get(url)
+                .header("User-Agent", USER_AGENT)
+                .send()
+                .await?;
+
+            res.error_for_status_ref()?;
+            read_text(res, MAX_PAYLOAD_SIZE).await
         })
-        .await
-        .unwrap()?;
+    }
+
+    fn handles(&self, link: &str) -> bool {
+        same_host(link, &self.base_url)
+    }
 
-    res.error_for_status_ref()?;
-    let link = res.headers()["location"].to_str().unwrap().to_string();
-    Ok(link)
+    fn expiry_days(&self) -> Option<u64> {
+        Some(DPASTE_EXPIRY_DAYS)
+    }
 }
 
-pub(crate) async fn get(
-    client: Arc<Mutex<reqwest::Client>>,
-    link: String,
-) -> Result<String, reqwest::Error> {
-    let rt = runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
+pub(crate) struct Hastebin {
+    base_url: String,
+}
 
-    let res = rt
-        .spawn(async move {
-            let client = client.lock().await;
+impl Hastebin {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl ShareBackend for Hastebin {
+    fn post(
+        &self,
+        client: reqwest::Client,
+        content: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let base_url = self.base_url.clone();
+        Box::pin(async move {
+            let res = client
+                .post(format!("{base_url}/documents"))
+                .header("User-Agent", USER_AGENT)
+                .body(content)
+                .send()
+                .await?;
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            res.error_for_status_ref()?;
+            let body: Value = serde_json::from_str(&res.text().await?)
+                .map_err(|_| ShareError::MalformedResponse)?;
+            let key = body["key"].as_str().ok_or(ShareError::MalformedResponse)?;
+            Ok(format!("{base_url}/{key}"))
+        })
+    }
 
-            client
-                .get(format!("{link}.txt"))
-                .header(
-                    "User-Agent",
-                    "League Config Manager - Settings manager for League of Legends",
-                )
+    fn get(
+        &self,
+        client: reqwest::Client,
+        link: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let url = format!("{}/raw/{}", self.base_url, last_segment(&link));
+        Box::pin(async move {
+            let res = client
+                .get(url)
+                .header("User-Agent", USER_AGENT)
                 .send()
-                .await
+                .await?;
+
+            res.error_for_status_ref()?;
+            read_text(res, MAX_PAYLOAD_SIZE).await
+        })
+    }
+
+    fn handles(&self, link: &str) -> bool {
+        same_host(link, &self.base_url)
+    }
+}
+
+pub(crate) struct Gist {
+    api_url: String,
+    token: String,
+}
+
+impl Gist {
+    pub fn new(api_url: &str, token: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let builder = builder
+            .header("User-Agent", USER_AGENT)
+            .header("Accept", "application/vnd.github+json");
+        if self.token.is_empty() {
+            builder
+        } else {
+            builder.bearer_auth(&self.token)
+        }
+    }
+}
+
+impl ShareBackend for Gist {
+    fn post(
+        &self,
+        client: reqwest::Client,
+        content: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let body = json!({
+            "description": "League Config Manager profile",
+            "public": false,
+            "files": { GIST_FILE: { "content": content } },
+        });
+        let req = self
+            .request(client.post(format!("{}/gists", self.api_url)))
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        Box::pin(async move {
+            let res = req.send().await?;
+
+            res.error_for_status_ref()?;
+            let body: Value = serde_json::from_str(&res.text().await?)
+                .map_err(|_| ShareError::MalformedResponse)?;
+            let link = body["html_url"]
+                .as_str()
+                .ok_or(ShareError::MalformedResponse)?;
+            Ok(link.to_string())
+        })
+    }
+
+    fn get(
+        &self,
+        client: reqwest::Client,
+        link: String,
+    ) -> BoxFuture<'static, Result<String, ShareError>> {
+        let req =
+            self.request(client.get(format!("{}/gists/{}", self.api_url, last_segment(&link))));
+        Box::pin(async move {
+            let res = req.send().await?;
+
+            res.error_for_status_ref()?;
+            // the payload is escaped once more inside of the gist JSON
+            let body: Value = serde_json::from_str(&read_text(res, 2 * MAX_PAYLOAD_SIZE).await?)
+                .map_err(|_| ShareError::MalformedResponse)?;
+            let files = body["files"]
+                .as_object()
+                .ok_or(ShareError::MalformedResponse)?;
+            let file = files
+                .get(GIST_FILE)
+                .or_else(|| files.values().next())
+                .ok_or(ShareError::MalformedResponse)?;
+            let content = file["content"]
+                .as_str()
+                .ok_or(ShareError::MalformedResponse)?;
+            Ok(content.to_string())
+        })
+    }
+
+    fn handles(&self, link: &str) -> bool {
+        same_host(link, "https://gist.github.com") || same_host(link, &self.api_url)
+    }
+}
+
+/// Client used for every paste service request, clones share its connection pool.
+pub(crate) fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Uploads `content` to the configured service, ends with the link to share.
+pub(crate) fn post(
+    client: reqwest::Client,
+    service: &ShareService,
+    content: String,
+) -> impl Stream<Item = Progress> {
+    let backend = service.backend();
+    with_retry(RETRY_DELAY, move || {
+        backend.post(client.clone(), content.clone())
+    })
+}
+
+/// Downloads the content behind `link` from whichever service made it.
+pub(crate) fn get(
+    client: reqwest::Client,
+    service: &ShareService,
+    link: String,
+) -> impl Stream<Item = Progress> {
+    let backend = service.backend_for(&link);
+    with_retry(RETRY_DELAY, move || {
+        backend.get(client.clone(), link.clone())
+    })
+}
+
+/// Runs `request` until it succeeds, fails for good or runs out of attempts, waiting
+/// `delay` before the first retry and twice as long before every next one.
+fn with_retry(
+    mut delay: Duration,
+    request: impl Fn() -> BoxFuture<'static, Result<String, ShareError>> + Send + 'static,
+) -> impl Stream<Item = Progress> {
+    stream::channel(MAX_ATTEMPTS as usize, move |mut output| async move {
+        let mut attempt = 1;
+        let result = loop {
+            match request().await {
+                Err(error) if error.is_transient() && attempt < MAX_ATTEMPTS => {
+                    let _ = output
+                        .send(Progress::Retrying {
+                            attempt,
+                            delay,
+                            error,
+                        })
+                        .await;
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    delay *= 2;
+                }
+                result => break result,
+            }
+        };
+        let _ = output.send(Progress::Finished(result)).await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use iced::futures::StreamExt;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::*;
+
+    /// Local HTTP stand-in answering a single request, returns its url and the received request.
+    async fn serve_once(status: &str, headers: &str, body: &str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let response = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 16 * 1024];
+            let read = socket.read(&mut buffer).await.unwrap();
+            // the client hangs up early on bodies it refuses
+            let _ = socket.write_all(response.as_bytes()).await;
+            String::from_utf8_lossy(&buffer[..read]).to_string()
+        });
+        (url, handle)
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn dpaste_post_reads_location() {
+        let (url, request) =
+            serve_once("201 Created", "Location: https://dpaste.com/ABC\r\n", "").await;
+        let link = Dpaste::new(&url)
+            .post(client(), "content".to_string())
+            .await
+            .unwrap();
+        assert_eq!(link, "https://dpaste.com/ABC");
+        assert!(request.await.unwrap().starts_with("POST /api/ "));
+    }
+
+    #[tokio::test]
+    async fn hastebin_post_and_get() {
+        let (url, request) = serve_once("200 OK", "", r#"{"key":"abc"}"#).await;
+        let link = Hastebin::new(&url)
+            .post(client(), "content".to_string())
+            .await
+            .unwrap();
+        assert_eq!(link, format!("{url}/abc"));
+        assert!(request.await.unwrap().starts_with("POST /documents "));
+
+        let (url, request) = serve_once("200 OK", "", "content").await;
+        let content = Hastebin::new(&url)
+            .get(client(), format!("{url}/abc"))
+            .await
+            .unwrap();
+        assert_eq!(content, "content");
+        assert!(request.await.unwrap().starts_with("GET /raw/abc "));
+    }
+
+    #[tokio::test]
+    async fn gist_get_reads_file_content() {
+        let body = json!({ "files": { GIST_FILE: { "content": "content" } } }).to_string();
+        let (url, request) = serve_once("200 OK", "", &body).await;
+        let content = Gist::new(&url, "")
+            .get(client(), "https://gist.github.com/user/123".to_string())
+            .await
+            .unwrap();
+        assert_eq!(content, "content");
+        assert!(request.await.unwrap().starts_with("GET /gists/123 "));
+    }
+
+    #[tokio::test]
+    async fn status_codes_become_typed_errors() {
+        let (url, _) = serve_once("429 Too Many Requests", "", "").await;
+        let result = Dpaste::new(&url)
+            .post(client(), "content".to_string())
+            .await;
+        assert_eq!(result, Err(ShareError::RateLimited));
+
+        let (url, _) = serve_once("404 Not Found", "", "").await;
+        let result = Hastebin::new(&url)
+            .get(client(), format!("{url}/abc"))
+            .await;
+        assert_eq!(result, Err(ShareError::Status(404)));
+    }
+
+    #[tokio::test]
+    async fn oversized_downloads_are_refused() {
+        let body = "a".repeat(MAX_PAYLOAD_SIZE as usize + 1);
+        let (url, _) = serve_once("200 OK", "", &body).await;
+        let result = Hastebin::new(&url)
+            .get(client(), format!("{url}/abc"))
+            .await;
+        assert_eq!(result, Err(ShareError::TooLarge));
+    }
+
+    #[tokio::test]
+    async fn retries_only_transient_failures() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let progress: Vec<Progress> = with_retry(Duration::ZERO, move || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                match attempt {
+                    0 => Err(ShareError::Status(503)),
+                    _ => Ok("link".to_string()),
+                }
+            })
+        })
+        .collect()
+        .await;
+        assert!(matches!(progress[0], Progress::Retrying { attempt: 1, .. }));
+        assert!(matches!(&progress[1], Progress::Finished(Ok(link)) if link == "link"));
+
+        let progress: Vec<Progress> = with_retry(Duration::ZERO, || {
+            Box::pin(async { Err(ShareError::Status(404)) })
         })
-        .await
-        .unwrap()?;
+        .collect()
+        .await;
+        assert!(matches!(
+            progress[..],
+            [Progress::Finished(Err(ShareError::Status(404)))]
+        ));
+    }
+
+    #[test]
+    fn display_leaves_out_the_token() {
+        let service = ShareService::Gist {
+            api_url: GITHUB_API_URL.to_string(),
+            token: "ghp_secret".to_string(),
+        };
+        assert!(!service.to_string().contains("ghp_secret"));
+    }
 
-    res.error_for_status_ref()?;
+    #[tokio::test]
+    async fn dpaste_get_uses_the_paste_id() {
+        for link in ["https://dpaste.com/ABC.txt", "https://dpaste.com/ABC/"] {
+            let (url, request) = serve_once("200 OK", "", "content").await;
+            Dpaste::new(&url)
+                .get(client(), link.to_string())
+                .await
+                .unwrap();
+            assert!(request.await.unwrap().starts_with("GET /ABC.txt "));
+        }
+    }
 
-    res.text().await
+    #[test]
+    fn links_pick_their_backend() {
+        let service = ShareService::Hastebin {
+            base_url: "http://localhost:7777".to_string(),
+        };
+        assert!(service
+            .backend_for("https://dpaste.com/ABC")
+            .handles("https://dpaste.com/ABC"));
+        assert!(service
+            .backend_for("http://localhost:7777/abc")
+            .handles("http://localhost:7777/abc"));
+        assert!(!Dpaste::new(DPASTE_URL).handles("https://dpaste.com.example.org/ABC"));
+        assert!(!Gist::new(GITHUB_API_URL, "").handles("https://example.org/gist.github.com/1"));
+    }
 }
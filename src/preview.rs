@@ -0,0 +1,109 @@
+use crate::game_settings::SettingsFiles;
+
+/// Settings shown at the top of the import preview.
+const KEY_SETTINGS: [(&str, &str); 16] = [
+    ("Window mode", "General/WindowMode"),
+    ("HUD scale", "HUD/GlobalScale"),
+    ("Minimap scale", "HUD/MinimapScale"),
+    ("Camera lock mode", "HUD/CameraLockMode"),
+    ("Camera move speed", "HUD/MapScrollSpeed"),
+    ("Frame cap", "Performance/FrameCapType"),
+    ("Character quality", "Performance/CharacterQuality"),
+    ("Environment quality", "Performance/EnvironmentQuality"),
+    ("Effects quality", "Performance/EffectsQuality"),
+    ("Shadow quality", "Performance/ShadowQuality"),
+    ("Ability 1", "Input.ini/GameEvents/evtCastSpell1"),
+    ("Ability 2", "Input.ini/GameEvents/evtCastSpell2"),
+    ("Ability 3", "Input.ini/GameEvents/evtCastSpell3"),
+    ("Ability 4", "Input.ini/GameEvents/evtCastSpell4"),
+    ("Summoner 1", "Input.ini/GameEvents/evtCastAvatarSpell1"),
+    ("Summoner 2", "Input.ini/GameEvents/evtCastAvatarSpell2"),
+];
+
+/// Resolution is never imported, see `GameSettings::reset_resolution`.
+const IGNORED: [&str; 2] = ["General/Width", "General/Height"];
+
+#[derive(Debug, Clone)]
+pub(crate) struct KeySetting {
+    pub(crate) label: &'static str,
+    pub(crate) value: Option<String>,
+    pub(crate) live: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Change {
+    pub(crate) key: String,
+    pub(crate) live: Option<String>,
+    pub(crate) value: Option<String>,
+}
+
+/// What importing a profile would change, compared to the live config when there is one.
+#[derive(Debug, Clone)]
+pub(crate) struct Preview {
+    pub(crate) name: String,
+    pub(crate) key_settings: Vec<KeySetting>,
+    pub(crate) changes: Vec<Change>,
+    pub(crate) compared: bool,
+}
+
+impl Preview {
+    pub fn new(name: &str, incoming: &SettingsFiles, live: Option<&SettingsFiles>) -> Self {
+        let incoming = incoming.values();
+        let live_values = live.map(|l| l.values()).unwrap_or_default();
+
+        let key_settings = KEY_SETTINGS
+            .iter()
+            .map(|(label, key)| KeySetting {
+                label,
+                value: incoming.get(*key).cloned(),
+                live: live_values.get(*key).cloned(),
+            })
+            .collect();
+
+        let mut changes = vec![];
+        if live.is_some() {
+            let mut keys: Vec<&String> = incoming.keys().chain(live_values.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let value = incoming.get(key);
+                let live = live_values.get(key);
+                if value != live && !IGNORED.contains(&key.as_str()) {
+                    changes.push(Change {
+                        key: key.clone(),
+                        live: live.cloned(),
+                        value: value.cloned(),
+                    });
+                }
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            key_settings,
+            changes,
+            compared: live.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(game: &str) -> SettingsFiles {
+        SettingsFiles::from_bytes(game.into(), "{\"files\":[]}".into()).unwrap()
+    }
+
+    #[test]
+    fn diff_against_live() {
+        let incoming = files("[General]\nWidth=1920\n[HUD]\nGlobalScale=0.5\nMinimapScale=1\n");
+        let live = files("[General]\nWidth=2560\n[HUD]\nGlobalScale=0.8\nMinimapScale=1\n");
+        let preview = Preview::new("mid", &incoming, Some(&live));
+
+        assert_eq!(preview.changes.len(), 1);
+        assert_eq!(preview.changes[0].key, "HUD/GlobalScale");
+        assert_eq!(preview.changes[0].live.as_deref(), Some("0.8"));
+        assert_eq!(preview.key_settings[1].value.as_deref(), Some("0.5"));
+    }
+}
@@ -1,20 +1,29 @@
+mod draft;
+mod manifest;
 mod settings;
 mod view;
 
+pub use draft::ProfileDraft;
+
 use std::{
-    error::Error,
+    collections::BTreeMap,
     fs::{self, File},
-    io::{Read, Write},
-    path::PathBuf,
+    io::{self, Seek, Write},
+    path::{Component, Path, PathBuf},
 };
 
-use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use manifest::{Bundle, Manifest, BUNDLE_FILE, BUNDLE_NAME, MANIFEST_FILE};
+use settings::LinkInfo;
 
 use crate::{
     champion::{get_champion_id_from_name, get_champion_name_from_id, get_champion_name_list},
+    clock,
     config::Config,
     error,
-    game_settings::GameSettings,
+    game_settings::{GameSettings, SettingsFiles, GAME_FILE, SETTINGS_FILE},
+    versioned::Versioned,
 };
 
 #[derive(Debug, Clone)]
@@ -25,6 +34,20 @@ pub struct Profile {
     champion: Option<u32>,
     game_settings: GameSettings,
     last_link: String,
+    link_info: Option<LinkInfo>,
+    files_hash: String,
+}
+
+/// Whether the last generated link can still be shared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkStatus {
+    Missing,
+    Current,
+    /// Generated before link tracking existed, so nothing is known about it.
+    Untracked,
+    Expired,
+    /// The profile changed after the link was generated.
+    Outdated,
 }
 
 //name gen
@@ -40,6 +63,14 @@ impl Profile {
         None
     }
 
+    /// Imported and typed names become folder names, so they have to be a single plain component.
+    fn valid_name(name: &str) -> bool {
+        let mut components = Path::new(name).components();
+        matches!(components.next(), Some(Component::Normal(c)) if c == name)
+            && components.next().is_none()
+            && !name.contains(['/', '\\'])
+    }
+
     fn check_name(name: &String, profiles: &Vec<Profile>) -> bool {
         for p in profiles {
             if p.name().eq(name) {
@@ -52,90 +83,82 @@ impl Profile {
 
 //constructors
 impl Profile {
-    pub fn new(cfg: &GameSettings) -> Self {
-        let name = Profile::gen_name().unwrap();
-        let mut dir = Config::get_config_dir();
-        dir.push(&name);
-        fs::create_dir_all(&dir).unwrap();
-        fs::copy(&cfg.game, dir.join(cfg.game.file_name().unwrap())).unwrap();
-        fs::copy(&cfg.settings, dir.join(cfg.settings.file_name().unwrap())).unwrap();
-        let game_settings = GameSettings::from_path(&dir).unwrap();
-        Self {
+    /// Creates a profile from the live settings in `cfg`.
+    pub fn new(cfg: &GameSettings) -> Result<Self, error::Error> {
+        let name = Profile::gen_name().ok_or(error::Error::NameTaken)?;
+        let live = SettingsFiles::from_paths(&cfg.game, &cfg.settings)?;
+        let dir = Config::get_config_dir().join(&name);
+        fs::create_dir_all(&dir).map_err(error::Error::write(&dir))?;
+        let game_settings = match GameSettings::from_files(&dir, &live) {
+            Ok(game_settings) => game_settings,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+        };
+        let profile = Self {
             name,
             editing: false,
             edit_name: String::from(""),
             champion: None,
             last_link: "".to_string(),
+            link_info: None,
+            files_hash: game_settings.files_hash(),
             game_settings,
-        }
+        };
+        profile.init_settings(None)?;
+        log::info!(profile:% = profile.name; "created profile");
+        Ok(profile)
     }
 
-    pub fn from_string(game_settings_string: String) -> Result<Self, crate::error::Error> {
-        let name = Profile::gen_name().unwrap();
-        let mut dir = Config::get_config_dir();
-        dir.push(&name);
-        fs::create_dir_all(&dir).unwrap();
-
-        let game_settings = GameSettings::from_string(&dir, game_settings_string)?;
-        // TODO: handle error
-        let _ = game_settings.reset_resolution();
-
-        Ok(Self {
-            name,
-            editing: false,
-            edit_name: String::from(""),
-            champion: None,
-            last_link: "".to_string(),
-            game_settings,
-        })
-    }
-
-    pub fn from_zip(zip_file_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
-        let zip_file = File::open(zip_file_path)?;
-        let mut name = zip_file_path
-            .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        if !Self::check_name(&name, &Self::profiles()) {
-            name = Self::gen_name().unwrap();
+    /// Creates a profile from imported files, keeping the name and champion from the manifest
+    /// when they are still free.
+    fn import(
+        fallback_name: String,
+        manifest: Option<Manifest>,
+        files: &SettingsFiles,
+    ) -> Result<Self, error::Error> {
+        let profiles = Self::profiles();
+        let mut name = match &manifest {
+            Some(manifest) => manifest.name.clone(),
+            None => fallback_name,
+        };
+        if !Self::valid_name(&name) || !Self::check_name(&name, &profiles) {
+            name = Self::gen_name().ok_or(error::Error::NameTaken)?;
         }
 
-        let mut archive = ZipArchive::new(zip_file)?;
-        let extraction_dir = Config::get_config_dir().join(&name);
-        std::fs::create_dir(&extraction_dir)?;
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let file_name = file.name().to_owned();
-
-            // Create the path to the extracted file in the destination directory.
-            let target_path = extraction_dir.join(file_name);
-
-            // Create the destination directory if it does not exist.
-            if let Some(parent_dir) = target_path.parent() {
-                std::fs::create_dir_all(parent_dir)?;
+        let champion = manifest
+            .as_ref()
+            .and_then(|m| m.champion.as_deref())
+            .and_then(Self::champion_from_option)
+            .filter(|c| profiles.iter().all(|p| p.champion() != &Some(*c)));
+
+        let dir = Config::get_config_dir().join(&name);
+        fs::create_dir(&dir).map_err(error::Error::write(&dir))?;
+
+        let written = GameSettings::from_files(&dir, files)
+            .and_then(|game_settings| game_settings.reset_resolution().map(|_| game_settings));
+        let game_settings = match written {
+            Ok(game_settings) => game_settings,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(e);
             }
+        };
 
-            let mut output_file = File::create(&target_path)?;
-
-            // Read the contents of the file from the ZIP archive and write them to the destination file.
-            std::io::copy(&mut file, &mut output_file)?;
-        }
-
-        let game_settings = GameSettings::from_path(&extraction_dir).unwrap();
-        // TODO: handle error
-        let _ = game_settings.reset_resolution();
-
-        Ok(Self {
+        let profile = Self {
             name,
             editing: false,
             edit_name: String::from(""),
-            champion: None,
+            champion,
             last_link: "".to_string(),
+            link_info: None,
+            files_hash: game_settings.files_hash(),
             game_settings,
-        })
+        };
+        profile.init_settings(manifest)?;
+        log::info!(profile:% = profile.name; "imported profile");
+        Ok(profile)
     }
 }
 
@@ -144,19 +167,100 @@ impl Profile {
         &self.name
     }
 
+    /// The last generated link while it still works, whole profiles are too big for a QR code.
+    pub fn qr_link(&self) -> Option<&str> {
+        match self.link_status(clock::now()) {
+            LinkStatus::Current | LinkStatus::Untracked => Some(&self.last_link),
+            _ => None,
+        }
+    }
+
+    /// Content of this profile for links and share codes.
+    pub fn paste_string(&self) -> Result<String, error::Error> {
+        self.game_settings.to_paste_string(&self.name)
+    }
+
+    pub fn link_status(&self, now: u64) -> LinkStatus {
+        if self.last_link.is_empty() {
+            return LinkStatus::Missing;
+        }
+        let Some(info) = &self.link_info else {
+            return LinkStatus::Untracked;
+        };
+        if info.expires.is_some_and(|expires| expires <= now) {
+            LinkStatus::Expired
+        } else if info.content_hash != self.files_hash {
+            LinkStatus::Outdated
+        } else {
+            LinkStatus::Current
+        }
+    }
+
+    /// Profiles that could be loaded, broken ones are left out.
     pub fn profiles() -> Vec<Profile> {
+        Self::load_all().0
+    }
+
+    /// Every profile that could be loaded, with an error for each broken profile folder and
+    /// each profile whose settings had to be reset.
+    pub fn load_all() -> (Vec<Profile>, Vec<error::Error>) {
         let config_dir = Config::get_config_dir();
         let mut profiles: Vec<Self> = vec![];
-        for entry in fs::read_dir(config_dir).unwrap() {
-            let entry = entry.unwrap().path();
+        let mut errors = vec![];
+        let entries = match fs::read_dir(&config_dir) {
+            Ok(entries) => entries,
+            Err(e) => return (profiles, vec![error::Error::read(&config_dir)(e)]),
+        };
+        for entry in entries.flatten() {
+            let entry = entry.path();
             if entry.is_dir() {
-                let name = entry.file_name().unwrap().to_str().unwrap().to_string();
+                let Some(file_name) = entry.file_name() else {
+                    continue;
+                };
+                let Some(name) = file_name.to_str().map(str::to_string) else {
+                    let cause = io::Error::new(io::ErrorKind::InvalidData, "name isn't valid text");
+                    errors.push(error::Error::BrokenProfile(
+                        file_name.to_string_lossy().to_string(),
+                        Box::new(error::Error::read(&entry)(cause)),
+                    ));
+                    continue;
+                };
+
+                GameSettings::recover(&entry);
+                let game_settings = match GameSettings::from_path(&entry) {
+                    Ok(game_settings) => game_settings,
+                    Err(e) => {
+                        // why a file is missing says more than the folder being wrong
+                        let cause = [GAME_FILE, SETTINGS_FILE]
+                            .iter()
+                            .map(|file_name| entry.join(file_name))
+                            .find_map(|path| {
+                                fs::metadata(&path).err().map(error::Error::read(&path))
+                            })
+                            .unwrap_or(e);
+                        errors.push(error::Error::BrokenProfile(name, Box::new(cause)));
+                        continue;
+                    }
+                };
 
                 let settings_path = entry.join("settings.json");
 
-                let settings = settings::Settings::from_path(&settings_path);
-
-                let game_settings = GameSettings::from_path(&entry).unwrap();
+                let settings = match settings::Settings::read(&settings_path) {
+                    Versioned::Read(settings) => settings,
+                    Versioned::Missing => settings::Settings::default(),
+                    Versioned::Unreadable(backup) => {
+                        errors.push(error::Error::ProfileSettingsReset(name.clone(), backup));
+                        settings::Settings::default()
+                    }
+                    // loading the profile anyway would save the defaults over its settings
+                    Versioned::Failed(e) => {
+                        errors.push(error::Error::BrokenProfile(
+                            name,
+                            Box::new(error::Error::read(&settings_path)(e)),
+                        ));
+                        continue;
+                    }
+                };
 
                 profiles.push(Self {
                     name,
@@ -164,11 +268,13 @@ impl Profile {
                     edit_name: String::from(""),
                     champion: settings.champion,
                     last_link: settings.last_link,
+                    link_info: settings.link_info,
+                    files_hash: game_settings.files_hash(),
                     game_settings,
                 })
             }
         }
-        profiles
+        (profiles, errors)
     }
 
     fn path(&self) -> PathBuf {
@@ -176,20 +282,36 @@ impl Profile {
         dir.join(&self.name)
     }
 
-    pub fn delete(&self) {
+    pub fn delete(&self) -> Result<(), error::Error> {
         let dir = self.path();
-        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(&dir).map_err(error::Error::write(&dir))?;
+        log::info!(profile:% = self.name; "deleted profile");
+        Ok(())
+    }
+
+    /// Writes the settings of this profile over `cfg`, both or neither, see
+    /// [`GameSettings::write`].
+    pub fn copy_files(&self, cfg: &GameSettings) -> Result<(), error::Error> {
+        cfg.write(&SettingsFiles::from_dir(&self.path())?)
     }
 
-    pub fn copy_files(&self, cfg: &GameSettings) {
-        cfg.set_readonly(false);
+    /// Whether `cfg` has the settings of this profile, leaving out `ignored` ones.
+    pub fn matches(&self, cfg: &GameSettings, ignored: &BTreeMap<String, String>) -> bool {
+        let values = |dir: Option<&Path>| {
+            let mut values = SettingsFiles::from_dir(dir?).ok()?.values();
+            values.retain(|key, _| !ignored.contains_key(key));
+            Some(values)
+        };
+        let profile = values(Some(&self.path()));
+        profile.is_some() && profile == values(cfg.game.parent())
+    }
 
-        fs::copy(self.path().join(cfg.game.file_name().unwrap()), &cfg.game).unwrap();
-        fs::copy(
-            self.path().join(cfg.settings.file_name().unwrap()),
-            &cfg.settings,
-        )
-        .unwrap();
+    /// Replaces the settings of this profile with the ones in `cfg`.
+    pub fn save_from(&mut self, cfg: &GameSettings) -> Result<(), error::Error> {
+        let dir = cfg.game.parent().ok_or(error::Error::WrongPath)?;
+        GameSettings::from_files(&self.path(), &SettingsFiles::from_dir(dir)?)?;
+        self.files_hash = self.game_settings.files_hash();
+        Ok(())
     }
 
     pub fn edit_start(&mut self) {
@@ -198,14 +320,19 @@ impl Profile {
     }
 
     pub fn edit_confirm(&mut self) -> Result<(), error::Error> {
+        if !Self::valid_name(&self.edit_name) {
+            return Err(error::Error::InvalidName);
+        }
         for p in Profile::profiles() {
             if p.name().eq(&self.edit_name) {
                 return Err(error::Error::NameTaken);
             }
         }
-        self.editing = false;
         let dir = Config::get_config_dir();
-        fs::rename(dir.join(&self.name), dir.join(&self.edit_name)).unwrap();
+        fs::rename(dir.join(&self.name), dir.join(&self.edit_name))
+            .map_err(error::Error::write(&dir.join(&self.name)))?;
+        log::info!(from:% = self.name, to:% = self.edit_name; "renamed profile");
+        self.editing = false;
         self.name.clone_from(&self.edit_name);
         self.game_settings.update_paths(&dir.join(&self.name));
         Ok(())
@@ -219,33 +346,100 @@ impl Profile {
         self.edit_name = new_name;
     }
 
-    pub fn zip(&self, export_dir: PathBuf) -> Result<PathBuf, Box<dyn Error>> {
+    pub fn zip(&self, export_dir: PathBuf) -> Result<PathBuf, error::Error> {
         let zip_file_path = export_dir.join(format!("{}.zip", &self.name));
-        let zip_file = File::create(&zip_file_path)?;
+        Self::create_zip(&zip_file_path, |zip_writer| {
+            self.write_to_zip(zip_writer, "", &zip_file_path)
+        })?;
+        Ok(zip_file_path)
+    }
 
+    /// Exports all profiles into a single bundle zip.
+    pub fn zip_bundle(profiles: &[Profile], export_dir: PathBuf) -> Result<PathBuf, error::Error> {
+        let zip_file_path = export_dir.join(format!("{BUNDLE_NAME}.zip"));
+        Self::create_zip(&zip_file_path, |zip_writer| {
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::DEFLATE);
+
+            let bundle = Bundle::new(profiles.iter().map(|p| p.name.clone()).collect());
+            zip_writer
+                .start_file(BUNDLE_FILE, options)
+                .map_err(write_error(&zip_file_path))?;
+            zip_writer
+                .write_all(
+                    bundle
+                        .to_json()
+                        .map_err(write_error(&zip_file_path))?
+                        .as_bytes(),
+                )
+                .map_err(error::Error::write(&zip_file_path))?;
+
+            for profile in profiles {
+                profile.write_to_zip(zip_writer, &format!("{}/", profile.name), &zip_file_path)?;
+            }
+            Ok(())
+        })?;
+        Ok(zip_file_path)
+    }
+
+    /// Writes a zip at `path` with `fill`, removing what was written when it fails.
+    fn create_zip(
+        path: &Path,
+        fill: impl FnOnce(&mut ZipWriter<File>) -> Result<(), error::Error>,
+    ) -> Result<(), error::Error> {
+        let zip_file = File::create(path).map_err(error::Error::write(path))?;
         let mut zip_writer = ZipWriter::new(zip_file);
 
-        let dir = Config::get_config_dir().join(&self.name);
+        let written = fill(&mut zip_writer)
+            .and_then(|()| zip_writer.finish().map_err(write_error(path)).map(|_| ()));
+        if written.is_err() {
+            let _ = fs::remove_file(path);
+        }
+        written
+    }
 
-        let files_to_compress: Vec<PathBuf> =
-            vec![dir.join("game.cfg"), dir.join("PersistedSettings.json")];
+    /// Adds the profile files and manifest to the zip at `zip_path`, prefixing their names
+    /// with `prefix`.
+    fn write_to_zip<W: Write + Seek>(
+        &self,
+        zip_writer: &mut ZipWriter<W>,
+        prefix: &str,
+        zip_path: &Path,
+    ) -> Result<(), error::Error> {
+        let dir = Config::get_config_dir().join(&self.name);
 
         let options = SimpleFileOptions::default().compression_method(CompressionMethod::DEFLATE);
 
-        for file_path in &files_to_compress {
-            let mut file = File::open(file_path)?;
-            let file_name = file_path.file_name().unwrap().to_str().unwrap();
+        for file_name in [GAME_FILE, SETTINGS_FILE] {
+            let path = dir.join(file_name);
+            let buffer = fs::read(&path).map_err(error::Error::read(&path))?;
 
             // Adding the file to the ZIP archive.
-            zip_writer.start_file(file_name, options)?;
+            zip_writer
+                .start_file(format!("{prefix}{file_name}"), options)
+                .map_err(write_error(zip_path))?;
+            zip_writer
+                .write_all(&buffer)
+                .map_err(error::Error::write(zip_path))?;
+        }
 
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)?;
+        zip_writer
+            .start_file(format!("{prefix}{MANIFEST_FILE}"), options)
+            .map_err(write_error(zip_path))?;
+        let manifest = self.manifest().to_json().map_err(write_error(zip_path))?;
+        zip_writer
+            .write_all(manifest.as_bytes())
+            .map_err(error::Error::write(zip_path))?;
 
-            zip_writer.write_all(&buffer)?;
-        }
+        Ok(())
+    }
 
-        Ok(zip_file_path)
+    fn manifest(&self) -> Manifest {
+        let settings = settings::Settings::from_path(&self.settings_path()).unwrap_or_default();
+        let mut manifest = Manifest::new(self.name.clone());
+        manifest.created = settings.created;
+        manifest.champion = self.champion.map(|_| self.selected().to_string());
+        manifest
     }
 
     pub fn champion(&self) -> &Option<u32> {
@@ -258,42 +452,121 @@ impl Profile {
         options
     }
 
-    pub fn set_link(&mut self, link: String) {
+    /// Stores a newly generated link, `expiry_days` comes from the service that made it.
+    pub fn set_link(&mut self, link: String, expiry_days: Option<u64>) -> Result<(), error::Error> {
+        let created = clock::now();
         self.last_link = link;
-        self.save_settings();
+        self.link_info = Some(LinkInfo {
+            created,
+            expires: expiry_days.map(|days| created + days * 86400),
+            content_hash: self.files_hash.clone(),
+        });
+        self.save_settings()
+    }
+
+    fn settings_path(&self) -> PathBuf {
+        self.path().join("settings.json")
+    }
+
+    fn save_settings(&self) -> Result<(), error::Error> {
+        let path = self.settings_path();
+
+        let mut settings = settings::Settings::from_path(&path)?;
+        settings.champion = self.champion;
+        settings.last_link.clone_from(&self.last_link);
+        settings.link_info.clone_from(&self.link_info);
+
+        settings.export(&path)
     }
 
-    fn save_settings(&self) {
-        let settings = settings::Settings {
+    /// Writes settings for a newly created profile, keeping the metadata of an imported one.
+    fn init_settings(&self, manifest: Option<Manifest>) -> Result<(), error::Error> {
+        let mut settings = settings::Settings {
             champion: self.champion,
-            last_link: self.last_link.clone(),
+            created: Some(clock::today()),
+            ..Default::default()
         };
+        if let Some(manifest) = manifest {
+            if manifest.created.is_some() {
+                settings.created = manifest.created;
+            }
+        }
 
-        let path = self.path().join("settings.json");
-
-        settings.export(&path);
+        settings.export(&self.settings_path())
     }
 
     pub fn selected(&self) -> &'static str {
-        if self.champion.is_none() {
-            return "Disabled";
-        }
-        let champ = self.champion.unwrap();
-        if champ == 0 {
-            return "Default";
+        match self.champion {
+            None => "Disabled",
+            Some(0) => "Default",
+            // a champion id from a hand edited or newer settings file
+            Some(champ) => get_champion_name_from_id(champ).unwrap_or("Disabled"),
         }
-        get_champion_name_from_id(champ).unwrap()
     }
 
-    pub fn set_selected(&mut self, option: &str) {
-        let champion = match option {
+    fn champion_from_option(option: &str) -> Option<u32> {
+        match option {
             "Disabled" => None,
             "Default" => Some(0),
             _ => get_champion_id_from_name(option),
+        }
+    }
+
+    pub fn set_selected(&mut self, option: &str) -> Result<(), error::Error> {
+        self.champion = Self::champion_from_option(option);
+
+        self.save_settings()
+    }
+}
+
+/// [`error::Error::write`] for zip and JSON errors, which both turn into io errors.
+fn write_error<E: Into<io::Error>>(path: &Path) -> impl FnOnce(E) -> error::Error {
+    let write = error::Error::write(path);
+    move |e| write(e.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_names_are_single_components() {
+        assert!(Profile::valid_name("mid lane"));
+        assert!(!Profile::valid_name(""));
+        assert!(!Profile::valid_name(".."));
+        assert!(!Profile::valid_name("../profile"));
+        assert!(!Profile::valid_name("a/b"));
+    }
+
+    #[test]
+    fn link_status_flags_stale_links() {
+        let mut profile = Profile {
+            name: "mid".to_string(),
+            editing: false,
+            edit_name: String::new(),
+            champion: None,
+            game_settings: GameSettings {
+                game: PathBuf::new(),
+                settings: PathBuf::new(),
+            },
+            last_link: String::new(),
+            link_info: None,
+            files_hash: "1234abcd".to_string(),
         };
+        assert_eq!(profile.link_status(100), LinkStatus::Missing);
+
+        profile.last_link = "https://dpaste.com/ABC".to_string();
+        assert_eq!(profile.link_status(100), LinkStatus::Untracked);
 
-        self.champion = champion;
+        profile.link_info = Some(LinkInfo {
+            created: 0,
+            expires: Some(200),
+            content_hash: "1234abcd".to_string(),
+        });
+        assert_eq!(profile.link_status(100), LinkStatus::Current);
+        assert_eq!(profile.link_status(200), LinkStatus::Expired);
 
-        self.save_settings();
+        profile.files_hash = "ffffffff".to_string();
+        assert_eq!(profile.link_status(100), LinkStatus::Outdated);
     }
 }
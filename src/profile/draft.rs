@@ -0,0 +1,263 @@
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use zip::ZipArchive;
+
+use crate::{
+    config::Config,
+    error::Error,
+    game_settings::{SettingsFiles, GAME_FILE, MAX_FILE_SIZE, SETTINGS_FILE},
+    share_payload::SharePayload,
+};
+
+use super::{
+    manifest::{Bundle, Manifest},
+    Profile,
+};
+
+/// An imported profile that was read and validated but not saved yet, so it can be previewed.
+#[derive(Debug, Clone)]
+pub struct ProfileDraft {
+    name: String,
+    manifest: Option<Manifest>,
+    files: SettingsFiles,
+}
+
+impl ProfileDraft {
+    /// Reads content shared through a link or share code.
+    pub fn from_string(game_settings_string: &str) -> Result<Self, Error> {
+        let (name, files) = SharePayload::decode(game_settings_string)?;
+        Ok(Self::new(name, None, files))
+    }
+
+    /// Reads a profile zip, or every profile of a bundle zip.
+    pub fn from_zip(zip_file_path: &Path) -> Result<Vec<Self>, Error> {
+        let zip_file = File::open(zip_file_path).map_err(|_| Error::ZipImport)?;
+        let mut archive = ZipArchive::new(zip_file).map_err(|_| Error::ZipImport)?;
+
+        let mut drafts = vec![];
+        if let Some(bundle) = Bundle::from_archive(&mut archive) {
+            for name in bundle.profiles {
+                let prefix = format!("{name}/");
+                let files = Self::read_archive(&mut archive, &prefix)?;
+                let manifest = Manifest::from_archive(&mut archive, &prefix);
+                drafts.push(Self::new(name, manifest, files));
+            }
+        } else {
+            // zips exported before manifests existed are named after the file
+            let name = zip_file_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+            let files = Self::read_archive(&mut archive, "")?;
+            let manifest = Manifest::from_archive(&mut archive, "");
+            drafts.push(Self::new(name, manifest, files));
+        }
+        Ok(drafts)
+    }
+
+    /// Reads a folder holding the settings files, either a `Config` folder or the League of
+    /// Legends folder around it.
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        let config_dir = Config::game_settings_path(dir);
+        let (files_dir, name) = if config_dir.is_dir() {
+            (config_dir.as_path(), dir.file_name())
+        } else {
+            (dir, dir.file_name().filter(|n| *n != "Config"))
+        };
+        let files = SettingsFiles::from_dir(files_dir)?;
+
+        let name = name
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Self::new(name, None, files))
+    }
+
+    /// Reads loose `game.cfg` and `PersistedSettings.json` files, matched by their name and
+    /// else by their extension, later paths win.
+    pub fn from_files(paths: &[PathBuf]) -> Result<Self, Error> {
+        let find = |name: &str, extension: &str| {
+            let named = paths
+                .iter()
+                .rev()
+                .find(|p| p.file_name().is_some_and(|n| n.eq_ignore_ascii_case(name)));
+            named.or_else(|| {
+                paths.iter().rev().find(|p| {
+                    p.extension()
+                        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+                })
+            })
+        };
+        let (Some(game), Some(settings)) = (find(GAME_FILE, "cfg"), find(SETTINGS_FILE, "json"))
+        else {
+            return Err(Error::MissingSettingsFile);
+        };
+        let files = SettingsFiles::from_paths(game, settings)?;
+
+        Ok(Self::new("".to_string(), None, files))
+    }
+
+    fn new(fallback_name: String, manifest: Option<Manifest>, files: SettingsFiles) -> Self {
+        let name = match &manifest {
+            Some(manifest) => manifest.name.clone(),
+            None => fallback_name,
+        };
+        Self {
+            name,
+            manifest,
+            files,
+        }
+    }
+
+    /// Reads the settings files stored under `prefix`, ignoring every other entry.
+    fn read_archive<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        prefix: &str,
+    ) -> Result<SettingsFiles, Error> {
+        let mut game = None;
+        let mut settings = None;
+
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(|_| Error::ZipImport)?;
+            if file.enclosed_name().is_none() {
+                return Err(Error::UnsafeZipEntry);
+            }
+            if file.is_dir() {
+                continue;
+            }
+
+            let target = match file.name().strip_prefix(prefix) {
+                Some(GAME_FILE) => &mut game,
+                Some(SETTINGS_FILE) => &mut settings,
+                _ => continue,
+            };
+
+            if file.size() > MAX_FILE_SIZE {
+                return Err(Error::FileTooLarge);
+            }
+            let mut buffer = Vec::new();
+            // the declared size can't be trusted, read at most one byte past the limit
+            file.take(MAX_FILE_SIZE + 1)
+                .read_to_end(&mut buffer)
+                .map_err(|_| Error::ZipImport)?;
+            *target = Some(buffer);
+        }
+
+        match (game, settings) {
+            (Some(game), Some(settings)) => SettingsFiles::from_bytes(game, settings),
+            _ => Err(Error::MissingSettingsFile),
+        }
+    }
+
+    /// Name the profile gets when saved, unless it's taken by then.
+    pub fn name(&self) -> &str {
+        if self.name.is_empty() {
+            "New profile"
+        } else {
+            &self.name
+        }
+    }
+
+    pub fn files(&self) -> &SettingsFiles {
+        &self.files
+    }
+
+    /// Creates the profile folder.
+    pub fn save(self) -> Result<Profile, Error> {
+        Profile::import(self.name, self.manifest, &self.files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    use std::fs;
+
+    use crate::test_util::{temp_dir, GAME, SETTINGS};
+
+    fn archive(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip_writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip_writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            zip_writer.write_all(content.as_bytes()).unwrap();
+        }
+        ZipArchive::new(zip_writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_only_whitelisted_files() {
+        let mut archive = archive(&[
+            ("game.cfg", "[General]\nWidth=1920\n"),
+            ("PersistedSettings.json", "{\"files\":[]}"),
+            ("readme.exe", "MZ"),
+        ]);
+        let files = ProfileDraft::read_archive(&mut archive, "").unwrap();
+        assert_eq!(files.game, "[General]\nWidth=1920\n");
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        let mut archive = archive(&[
+            ("game.cfg", "[General]\n"),
+            ("PersistedSettings.json", "{}"),
+            ("../../game.cfg", "[General]\n"),
+        ]);
+        assert!(matches!(
+            ProfileDraft::read_archive(&mut archive, ""),
+            Err(Error::UnsafeZipEntry)
+        ));
+    }
+
+    #[test]
+    fn rejects_incomplete_and_invalid_archives() {
+        let mut archive_missing = archive(&[("game.cfg", "[General]\n")]);
+        assert!(matches!(
+            ProfileDraft::read_archive(&mut archive_missing, ""),
+            Err(Error::MissingSettingsFile)
+        ));
+
+        let mut archive_invalid = archive(&[
+            ("game.cfg", "[General]\n"),
+            ("PersistedSettings.json", "<html>"),
+        ]);
+        assert!(matches!(
+            ProfileDraft::read_archive(&mut archive_invalid, ""),
+            Err(Error::InvalidPersistedSettings)
+        ));
+    }
+
+    #[test]
+    fn loose_files_prefer_their_real_names() {
+        let dir = temp_dir("loose-files");
+        let paths = [
+            (GAME_FILE, GAME),
+            ("other.cfg", "[General]\nWidth=800\n"),
+            (SETTINGS_FILE, SETTINGS),
+        ]
+        .map(|(name, content)| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        });
+
+        let draft = ProfileDraft::from_files(&paths).unwrap();
+        assert_eq!(draft.files.game, GAME);
+        assert!(matches!(
+            ProfileDraft::from_files(&paths[..2]),
+            Err(Error::MissingSettingsFile)
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use std::io::{Read, Seek};
+
+use zip::ZipArchive;
+
+use crate::game_settings::MAX_FILE_SIZE;
+
+pub(crate) const MANIFEST_FILE: &str = "manifest.json";
+
+pub(crate) const BUNDLE_FILE: &str = "bundle.json";
+
+pub(crate) const BUNDLE_NAME: &str = "league_config_manager_profiles";
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Describes an exported profile, stored as `manifest.json` next to the game files in a zip.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Manifest {
+    pub(crate) format_version: u32,
+    pub(crate) name: String,
+    /// Champion the profile swaps on, as shown in the pick list ("Default" or a champion name).
+    #[serde(default)]
+    pub(crate) champion: Option<String>,
+    #[serde(default)]
+    pub(crate) created: Option<String>,
+    #[serde(default)]
+    pub(crate) app_version: String,
+}
+
+impl Manifest {
+    pub fn new(name: String) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            name,
+            champion: None,
+            created: None,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Reads the manifest under `prefix` from an archive, `None` for zips exported before
+    /// manifests existed.
+    pub fn from_archive<R: Read + Seek>(archive: &mut ZipArchive<R>, prefix: &str) -> Option<Self> {
+        let file = archive.by_name(&format!("{prefix}{MANIFEST_FILE}")).ok()?;
+        serde_json::from_reader(file.take(MAX_FILE_SIZE)).ok()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Index of a multi-profile zip, every listed profile is stored in a folder with its name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Bundle {
+    pub(crate) format_version: u32,
+    #[serde(default)]
+    pub(crate) app_version: String,
+    pub(crate) profiles: Vec<String>,
+}
+
+impl Bundle {
+    pub fn new(profiles: Vec<String>) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            profiles,
+        }
+    }
+
+    /// Reads the bundle index, `None` when the archive holds a single profile.
+    pub fn from_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Option<Self> {
+        let file = archive.by_name(BUNDLE_FILE).ok()?;
+        serde_json::from_reader(file.take(MAX_FILE_SIZE)).ok()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_manifest_fields_default() {
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"format_version":1,"name":"mid"}"#).unwrap();
+        assert_eq!(manifest.name, "mid");
+        assert!(manifest.champion.is_none());
+
+        // fields written by earlier exports are ignored
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"format_version":1,"name":"mid","description":"","author":"","source_patch":null}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "mid");
+    }
+}
@@ -1,42 +1,57 @@
 use serde::{Deserialize, Serialize};
 
-use std::io::BufReader;
-
-use std::fs::OpenOptions;
+use serde_json::{Map, Value};
 
 use std::path::Path;
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::{
+    error::Error,
+    versioned::{self, Migration, Versioned},
+};
+
+/// Index `n` upgrades a version `n` settings file to version `n + 1`.
+const MIGRATIONS: [Migration; 1] = [Settings::migrate_defaults];
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub(crate) struct Settings {
     pub(crate) champion: Option<u32>,
     pub(crate) last_link: String,
+    #[serde(default)]
+    pub(crate) created: Option<String>,
+    #[serde(default)]
+    pub(crate) link_info: Option<LinkInfo>,
+}
+
+/// When `last_link` was generated and what it was generated from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct LinkInfo {
+    /// Unix time in seconds.
+    pub(crate) created: u64,
+    /// Unix time in seconds, `None` for services that keep links forever.
+    pub(crate) expires: Option<u64>,
+    /// Hash of the profile files when the link was generated, see `GameSettings::files_hash`.
+    pub(crate) content_hash: String,
 }
 
 impl Settings {
-    pub fn from_path(path: &Path) -> Settings {
-        let settings_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(path)
-            .expect("Can't open profile settings file");
-        let reader = BufReader::new(settings_file);
-        let settings: Settings = serde_json::from_reader(reader).unwrap_or(Settings {
-            champion: None,
-            last_link: "".to_string(),
-        });
-        settings
+    pub fn read(path: &Path) -> Versioned<Settings> {
+        versioned::read(path, &MIGRATIONS)
+    }
+
+    /// Missing and unreadable settings are the defaults, an unreadable file is kept as a backup.
+    /// Fails when the file couldn't be read or moved aside, it mustn't be saved over then.
+    pub fn from_path(path: &Path) -> Result<Settings, Error> {
+        match Self::read(path) {
+            Versioned::Read(settings) => Ok(settings),
+            Versioned::Missing | Versioned::Unreadable(_) => Ok(Settings::default()),
+            Versioned::Failed(e) => Err(Error::read(path)(e)),
+        }
     }
 
-    pub fn export(&self, path: &Path) {
-        let settings_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)
-            .expect("Can't open profile settings file");
-        let writer = std::io::BufWriter::new(settings_file);
-        serde_json::to_writer(writer, self).expect("Couldn't write the profile settings");
+    pub fn export(&self, path: &Path) -> Result<(), Error> {
+        versioned::write(path, self, &MIGRATIONS).map_err(Error::write(path))
     }
+
+    /// Version 1 only added fields with defaults.
+    fn migrate_defaults(_settings: &mut Map<String, Value>) {}
 }
@@ -35,7 +35,7 @@ use crate::message::Message;
 
 use iced::Element;
 
-use super::Profile;
+use super::{LinkStatus, Profile};
 
 //view
 impl Profile {
@@ -51,17 +51,46 @@ impl Profile {
             .into()
     }
 
-    fn share_row(&self) -> Row<Message, Theme> {
-        let mut txt = self.last_link.clone();
-        if self.last_link.is_empty() {
-            txt = "No link generated".to_string();
-        }
-        let txt = tooltip(
-            text(txt),
-            "Last generated profile link",
-            tooltip::Position::Bottom,
-        )
-        .class(theme::Container::Tooltip);
+    fn share_row(&self, uploading: bool) -> Row<Message, Theme> {
+        let status = self.link_status(clock::now());
+        let info = self.link_info.as_ref();
+
+        let (txt, tip) = match status {
+            LinkStatus::Missing => (
+                "No link generated".to_string(),
+                "Last generated profile link".to_string(),
+            ),
+            LinkStatus::Untracked => (
+                self.last_link.clone(),
+                "Generated by an older version, it might have expired".to_string(),
+            ),
+            LinkStatus::Current => (
+                self.last_link.clone(),
+                match info.and_then(|i| i.expires) {
+                    Some(expires) => format!("Expires on {}", clock::date_from_unix(expires)),
+                    None => "Doesn't expire".to_string(),
+                },
+            ),
+            LinkStatus::Expired => (
+                format!("{} (expired)", self.last_link),
+                format!(
+                    "Expired on {}, generate a new link",
+                    clock::date_from_unix(info.and_then(|i| i.expires).unwrap_or_default())
+                ),
+            ),
+            LinkStatus::Outdated => (
+                format!("{} (outdated)", self.last_link),
+                "Profile changed after this link was generated, generate a new link".to_string(),
+            ),
+        };
+        let stale = matches!(status, LinkStatus::Expired | LinkStatus::Outdated);
+        let txt = text(txt).class(if stale {
+            theme::Text::Error
+        } else {
+            theme::Text::Default
+        });
+        let txt =
+            tooltip(txt, text(tip), tooltip::Position::Bottom).class(theme::Container::Tooltip);
 
         let copy_btn = tooltip(
             icon_btn(
@@ -77,23 +106,60 @@ impl Profile {
         let gen_btn = tooltip(
             icon_btn(
                 text("G").into(),
-                Some(Message::GenerateLink(
-                    self.game_settings.to_paste_string(),
-                    self.name().clone(),
-                )),
+                (!uploading).then(|| Message::GenerateLink(self.name.clone())),
                 colors::BLUE,
             ),
-            "Generate link",
+            if uploading {
+                "Generating link..."
+            } else if stale {
+                "Regenerate link"
+            } else {
+                "Generate link"
+            },
             tooltip::Position::Bottom,
         )
         .class(theme::Container::Tooltip);
 
-        row![txt, horizontal_space(), copy_btn, gen_btn]
+        let code_btn = tooltip(
+            icon_btn(
+                text("#").into(),
+                Some(Message::CopyCode(self.name.clone())),
+                colors::BLUE,
+            ),
+            "Copy share code (works without the paste service)",
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        let has_link = matches!(status, LinkStatus::Current | LinkStatus::Untracked);
+        let qr_btn = tooltip(
+            icon_btn(
+                text("Q").into(),
+                has_link.then(|| Message::ShowQr(self.name.clone())),
+                colors::BLUE,
+            ),
+            if has_link {
+                "Show link as a QR code"
+            } else {
+                "Generate a link first, a whole profile is too big for a QR code"
+            },
+            tooltip::Position::Bottom,
+        )
+        .class(theme::Container::Tooltip);
+
+        row![txt, horizontal_space(), copy_btn, gen_btn, code_btn, qr_btn]
             .align_y(Center)
             .spacing(10)
     }
 
-    pub fn view_profile(&self, cfg: &Option<GameSettings>) -> Element<Message, Theme> {
+    /// `uploading` disables the generate button while a link for this profile is generated,
+    /// `installs` is how many installs the profile can be used on.
+    pub fn view_profile(
+        &self,
+        cfg: &Option<GameSettings>,
+        uploading: bool,
+        installs: usize,
+    ) -> Element<Message, Theme> {
         let del_btn = tooltip(
             icon_btn(
                 trash_icon(),
@@ -175,10 +241,24 @@ impl Profile {
 
         profile_row = profile_row.push(edit_btn);
         profile_row = profile_row.push(use_btn);
+        if installs > 1 {
+            profile_row = profile_row.push(
+                tooltip(
+                    icon_btn(
+                        text("All").into(),
+                        Some(Message::UseProfileEverywhere(self.clone())),
+                        colors::BLUE,
+                    ),
+                    "Use this profile on every install",
+                    tooltip::Position::Bottom,
+                )
+                .class(theme::Container::Tooltip),
+            );
+        }
         profile_row = profile_row.push(export_btn);
         profile_row = profile_row.push(del_btn);
 
-        column![profile_row, self.champion_row(), self.share_row()]
+        column![profile_row, self.champion_row(), self.share_row(uploading)]
             .spacing(10)
             .into()
     }
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use iced::advanced::graphics::image::image_rs::{DynamicImage, GrayImage, ImageFormat, Luma};
+use iced::widget::image::Handle;
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::error::Error;
+
+/// Pixels per QR module.
+const SCALE: u32 = 6;
+
+/// Empty modules around the code, scanners need at least 4.
+const QUIET_ZONE: u32 = 4;
+
+/// A profile link or share code rendered as a QR code.
+#[derive(Debug, Clone)]
+pub(crate) struct ShareQr {
+    pub(crate) profile_name: String,
+    pub(crate) content: String,
+    pub(crate) handle: Handle,
+    image: GrayImage,
+}
+
+impl ShareQr {
+    /// Uses the lowest error correction so the longest share codes still fit.
+    pub fn new(profile_name: &str, content: &str) -> Result<Self, Error> {
+        let code = QrCode::with_error_correction_level(content, EcLevel::L)
+            .map_err(|_| Error::QrTooLarge)?;
+        let image = Self::render(&code);
+        let rgba = DynamicImage::ImageLuma8(image.clone()).to_rgba8();
+        let handle = Handle::from_rgba(image.width(), image.height(), rgba.into_raw());
+
+        Ok(Self {
+            profile_name: profile_name.to_string(),
+            content: content.to_string(),
+            handle,
+            image,
+        })
+    }
+
+    fn render(code: &QrCode) -> GrayImage {
+        let width = code.width() as u32;
+        let colors = code.to_colors();
+        let size = (width + 2 * QUIET_ZONE) * SCALE;
+
+        GrayImage::from_fn(size, size, |x, y| {
+            let (x, y) = (x / SCALE, y / SCALE);
+            let inside = (QUIET_ZONE..QUIET_ZONE + width).contains(&x)
+                && (QUIET_ZONE..QUIET_ZONE + width).contains(&y);
+            let dark = inside
+                && colors[((y - QUIET_ZONE) * width + x - QUIET_ZONE) as usize] == Color::Dark;
+            Luma([if dark { 0 } else { 255 }])
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), Error> {
+        self.image
+            .save_with_format(path, ImageFormat::Png)
+            .map_err(|_| Error::QrExport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{share_code, share_payload::SharePayload};
+
+    /// Roughly the size of a real client's settings, a few hundred keys with varied values.
+    fn realistic_profile() -> String {
+        let mut seed = 7u64;
+        let mut value = || {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            format!("{:.4}", (seed >> 33) as f64 / u32::MAX as f64)
+        };
+        let game: String = (0..150)
+            .map(|n| format!("Option{n}={}\n", value()))
+            .collect();
+        let settings: Vec<String> = (0..400)
+            .map(|n| format!("{{\"name\":\"Setting{n}\",\"value\":\"{}\"}}", value()))
+            .collect();
+        let settings = format!(
+            "{{\"files\":[{{\"name\":\"Game.cfg\",\"sections\":[{{\"name\":\"General\",\
+             \"settings\":[{}]}}]}}]}}",
+            settings.join(",")
+        );
+        SharePayload::new("mid", format!("[General]\n{game}"), settings).encode()
+    }
+
+    #[test]
+    fn whole_profiles_need_a_link() {
+        let code = share_code::encode(&realistic_profile());
+        assert!(matches!(ShareQr::new("mid", &code), Err(Error::QrTooLarge)));
+    }
+
+    #[test]
+    fn renders_with_quiet_zone() {
+        let qr = ShareQr::new("mid", "https://dpaste.com/ABCDEFGHI").unwrap();
+        // version 2 codes are 25 modules wide
+        assert_eq!(qr.image.width(), (25 + 2 * QUIET_ZONE) * SCALE);
+        assert_eq!(qr.image.get_pixel(0, 0), &Luma([255]));
+        assert_eq!(
+            qr.image.get_pixel(QUIET_ZONE * SCALE, QUIET_ZONE * SCALE),
+            &Luma([0])
+        );
+
+        assert!(matches!(
+            ShareQr::new("mid", &"A".repeat(8000)),
+            Err(Error::QrTooLarge)
+        ));
+    }
+}
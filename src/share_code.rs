@@ -0,0 +1,97 @@
+use std::io::{Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::{error::Error, game_settings::MAX_FILE_SIZE};
+
+const PREFIX: &str = "LCM";
+
+const VERSION: u32 = 1;
+
+/// Encodes the output of `GameSettings::to_paste_string` as a share code, which works without
+/// a paste service.
+///
+/// A code is `LCM<version>.` followed by base64url of the content crc32 and the deflated content.
+pub(crate) fn encode(content: &str) -> String {
+    let mut payload = crc32fast::hash(content.as_bytes()).to_be_bytes().to_vec();
+
+    let mut encoder = DeflateEncoder::new(&mut payload, Compression::best());
+    encoder
+        .write_all(content.as_bytes())
+        .and_then(|_| encoder.finish().map(|_| ()))
+        .expect("Writing to a Vec can't fail");
+
+    format!("{PREFIX}{VERSION}.{}", URL_SAFE_NO_PAD.encode(payload))
+}
+
+/// Whether `text` looks like a share code rather than a link.
+pub(crate) fn is_code(text: &str) -> bool {
+    text.trim()
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .is_some_and(|(version, _)| {
+            !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+/// Decodes a share code back into the shared content.
+pub(crate) fn decode(code: &str) -> Result<String, Error> {
+    let code: String = code.split_whitespace().collect();
+    let (version, data) = code
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .ok_or(Error::InvalidShareCode)?;
+    if version.parse::<u32>() != Ok(VERSION) {
+        return Err(Error::UnsupportedShareCode);
+    }
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|_| Error::InvalidShareCode)?;
+    if payload.len() < 4 {
+        return Err(Error::InvalidShareCode);
+    }
+    let (checksum, compressed) = payload.split_at(4);
+
+    let mut content = Vec::new();
+    // both files together can't be larger than this, stops codes that inflate endlessly
+    DeflateDecoder::new(compressed)
+        .take(2 * MAX_FILE_SIZE + 1)
+        .read_to_end(&mut content)
+        .map_err(|_| Error::InvalidShareCode)?;
+    if content.len() as u64 > 2 * MAX_FILE_SIZE {
+        return Err(Error::FileTooLarge);
+    }
+
+    if crc32fast::hash(&content).to_be_bytes() != checksum {
+        return Err(Error::InvalidShareCode);
+    }
+    String::from_utf8(content).map_err(|_| Error::InvalidShareCode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"{"format_version":1,"files":{"game.cfg":"[General]\n"}}"#;
+
+    #[test]
+    fn round_trip() {
+        let code = encode(CONTENT);
+        assert!(is_code(&code));
+        assert!(!is_code("https://dpaste.com/ABC"));
+        assert_eq!(decode(&code).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn rejects_damaged_codes() {
+        let mut code = encode(CONTENT);
+        code.pop();
+        assert!(matches!(decode(&code), Err(Error::InvalidShareCode)));
+        assert!(matches!(
+            decode(&code.replacen("LCM1", "LCM9", 1)),
+            Err(Error::UnsupportedShareCode)
+        ));
+    }
+}
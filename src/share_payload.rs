@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    game_settings::{content_hash, SettingsFiles, MAX_FILE_SIZE},
+};
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Both files escaped inside of JSON, anything bigger can't be a shared profile.
+pub(crate) const MAX_PAYLOAD_SIZE: u64 = 4 * MAX_FILE_SIZE;
+
+/// Separator between the two files in links generated before the JSON payload existed.
+const LEGACY_SEPERATOR: &str = "<lcm-seperator>";
+
+/// Content uploaded for a profile link or put into a share code.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SharePayload {
+    pub(crate) format_version: u32,
+    #[serde(default)]
+    pub(crate) app_version: String,
+    #[serde(default)]
+    pub(crate) profile_name: String,
+    pub(crate) files: SharedFiles,
+    pub(crate) hashes: SharedFiles,
+}
+
+/// One value per settings file, the contents in `files` and their crc32 in `hashes`.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SharedFiles {
+    #[serde(rename = "game.cfg")]
+    pub(crate) game: String,
+    #[serde(rename = "PersistedSettings.json")]
+    pub(crate) settings: String,
+}
+
+impl SharePayload {
+    pub fn new(profile_name: &str, game: String, settings: String) -> Self {
+        let hashes = SharedFiles {
+            game: content_hash(game.as_bytes()),
+            settings: content_hash(settings.as_bytes()),
+        };
+        Self {
+            format_version: FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            profile_name: profile_name.to_string(),
+            files: SharedFiles { game, settings },
+            hashes,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("Payload always serializes")
+    }
+
+    /// Decodes shared content into the profile name and validated files, accepting both the
+    /// JSON payload and the legacy `<lcm-seperator>` format.
+    ///
+    /// Nothing is written here, so a bad download never leaves files behind.
+    pub fn decode(content: &str) -> Result<(String, SettingsFiles), Error> {
+        if content.len() as u64 > MAX_PAYLOAD_SIZE {
+            return Err(Error::FileTooLarge);
+        }
+        let trimmed = content.trim_start();
+        // error and expired link pages come back as HTML instead of the paste
+        if trimmed.is_empty() || trimmed.starts_with('<') {
+            return Err(Error::NotSharedProfile);
+        }
+        // legacy content starts with game.cfg, which is never a JSON object
+        if !trimmed.starts_with('{') {
+            return Self::decode_legacy(content);
+        }
+
+        let payload: SharePayload =
+            serde_json::from_str(content).map_err(|_| Error::InvalidSharePayload)?;
+        if payload.format_version > FORMAT_VERSION {
+            return Err(Error::UnsupportedSharePayload);
+        }
+        if content_hash(payload.files.game.as_bytes()) != payload.hashes.game
+            || content_hash(payload.files.settings.as_bytes()) != payload.hashes.settings
+        {
+            return Err(Error::InvalidSharePayload);
+        }
+
+        let files = SettingsFiles::from_bytes(
+            payload.files.game.into_bytes(),
+            payload.files.settings.into_bytes(),
+        )?;
+        Ok((payload.profile_name, files))
+    }
+
+    fn decode_legacy(content: &str) -> Result<(String, SettingsFiles), Error> {
+        let (game, settings) = content
+            .split_once(LEGACY_SEPERATOR)
+            .ok_or(Error::NotSharedProfile)?;
+        let files = SettingsFiles::from_bytes(game.into(), settings.into())?;
+        Ok((String::new(), files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{GAME, SETTINGS};
+
+    #[test]
+    fn round_trip() {
+        let content = SharePayload::new("mid", GAME.into(), SETTINGS.into()).encode();
+        let (name, files) = SharePayload::decode(&content).unwrap();
+        assert_eq!(name, "mid");
+        assert_eq!(files.game, GAME);
+        assert_eq!(files.settings, SETTINGS);
+    }
+
+    #[test]
+    fn reads_legacy_format() {
+        let content = format!("{GAME}{LEGACY_SEPERATOR}{SETTINGS}");
+        let (name, files) = SharePayload::decode(&content).unwrap();
+        assert!(name.is_empty());
+        assert_eq!(files.settings, SETTINGS);
+    }
+
+    #[test]
+    fn separator_inside_a_file_survives() {
+        let game = format!("{GAME}; {LEGACY_SEPERATOR}\n");
+        let content = SharePayload::new("mid", game.clone(), SETTINGS.into()).encode();
+        assert_eq!(SharePayload::decode(&content).unwrap().1.game, game);
+    }
+
+    #[test]
+    fn rejects_pages_and_broken_files() {
+        assert!(matches!(
+            SharePayload::decode("<!DOCTYPE html><html></html>"),
+            Err(Error::NotSharedProfile)
+        ));
+        let content = SharePayload::new("mid", GAME.into(), "{\"files\":{}}".into()).encode();
+        assert!(matches!(
+            SharePayload::decode(&content),
+            Err(Error::InvalidPersistedSettings)
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_files() {
+        let mut payload = SharePayload::new("mid", GAME.into(), SETTINGS.into());
+        payload.files.game.push_str("Height=1080\n");
+        assert!(matches!(
+            SharePayload::decode(&payload.encode()),
+            Err(Error::InvalidSharePayload)
+        ));
+    }
+}
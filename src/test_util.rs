@@ -0,0 +1,13 @@
+use std::{fs, path::PathBuf};
+
+pub const GAME: &str = "[General]\nWidth=1920\n";
+
+pub const SETTINGS: &str = "{\"files\":[]}";
+
+/// Empty folder for one test, whatever a failed earlier run left in it is removed first.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lcm-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
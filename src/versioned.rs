@@ -0,0 +1,186 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+const VERSION_KEY: &str = "version";
+
+/// Upgrades a file from the version at its index in the migration list to the next one.
+pub(crate) type Migration = fn(&mut Map<String, Value>);
+
+/// Result of reading a versioned JSON file.
+#[derive(Debug)]
+pub(crate) enum Versioned<T> {
+    Missing,
+    Read(T),
+    /// The file couldn't be parsed or was written by a newer version, it was moved to this
+    /// backup so it isn't lost when the defaults are saved in its place.
+    Unreadable(PathBuf),
+    /// The file couldn't be read or moved aside, it's still there and mustn't be saved over.
+    Failed(io::Error),
+}
+
+/// Reads a JSON file with a `version` field, running `migrations` to bring older files up to
+/// the current version, which is the number of migrations. Files without the field are
+/// version 0.
+pub(crate) fn read<T: DeserializeOwned>(path: &Path, migrations: &[Migration]) -> Versioned<T> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Versioned::Missing,
+        Err(e) => return Versioned::Failed(e),
+    };
+    if content.trim().is_empty() {
+        return Versioned::Missing;
+    }
+
+    let Ok(Value::Object(mut object)) = serde_json::from_str(&content) else {
+        return unreadable(path);
+    };
+    let version = object.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0) as usize;
+    if version > migrations.len() {
+        return unreadable(path);
+    }
+    for migration in &migrations[version..] {
+        migration(&mut object);
+    }
+
+    match serde_json::from_value(Value::Object(object)) {
+        Ok(value) => Versioned::Read(value),
+        Err(_) => unreadable(path),
+    }
+}
+
+/// Writes `value` with the current version, the number of `migrations`. The file is written
+/// next to `path` first and then moved over it, so a crash can't leave half a file behind.
+pub(crate) fn write<T: Serialize>(
+    path: &Path,
+    value: &T,
+    migrations: &[Migration],
+) -> io::Result<()> {
+    let mut json = serde_json::to_value(value)?;
+    if let Value::Object(object) = &mut json {
+        object.insert(VERSION_KEY.to_string(), migrations.len().into());
+    }
+    let temp = with_suffix(path, ".tmp");
+    let mut file = io::BufWriter::new(fs::File::create(&temp)?);
+    serde_json::to_writer(&mut file, &json)?;
+    file.into_inner()?.sync_all()?;
+    fs::rename(temp, path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `config.json.bak`, or `config.json.2.bak` and so on when older backups are still there.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = with_suffix(path, ".bak");
+    let mut i = 2;
+    while backup.exists() {
+        backup = with_suffix(path, &format!(".{i}.bak"));
+        i += 1;
+    }
+    backup
+}
+
+fn unreadable<T>(path: &Path) -> Versioned<T> {
+    let backup = backup_path(path);
+    match fs::rename(path, &backup) {
+        Ok(()) => Versioned::Unreadable(backup),
+        Err(e) => Versioned::Failed(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    use crate::test_util::temp_dir;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct File {
+        name: String,
+    }
+
+    const MIGRATIONS: [Migration; 1] = [|object| {
+        if let Some(old) = object.remove("old_name") {
+            object.insert("name".to_string(), old);
+        }
+    }];
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = temp_dir(&format!("versioned-{name}")).join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn migrates_old_files() {
+        let path = temp_file("old.json", r#"{"old_name":"mid"}"#);
+        let Versioned::Read(file) = read::<File>(&path, &MIGRATIONS) else {
+            panic!("old file wasn't migrated");
+        };
+        assert_eq!(file.name, "mid");
+
+        write(&path, &file, &MIGRATIONS).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains(r#""version":1"#));
+        assert!(matches!(
+            read::<File>(&path, &MIGRATIONS),
+            Versioned::Read(File { .. })
+        ));
+    }
+
+    #[test]
+    fn keeps_unreadable_files_as_backup() {
+        for (name, content) in [
+            ("broken.json", "{\"name\":"),
+            ("newer.json", r#"{"name":"mid","version":7}"#),
+        ] {
+            let path = temp_file(name, content);
+            let Versioned::Unreadable(backup) = read::<File>(&path, &MIGRATIONS) else {
+                panic!("{name} wasn't moved aside");
+            };
+            assert!(!path.exists());
+            assert_eq!(fs::read_to_string(backup).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn older_backups_are_kept() {
+        let path = temp_file("config.json", "{");
+        fs::write(with_suffix(&path, ".bak"), "older").unwrap();
+
+        let Versioned::Unreadable(backup) = read::<File>(&path, &MIGRATIONS) else {
+            panic!("file wasn't moved aside");
+        };
+        assert_eq!(backup, with_suffix(&path, ".2.bak"));
+        assert_eq!(fs::read_to_string(backup).unwrap(), "{");
+        assert_eq!(
+            fs::read_to_string(with_suffix(&path, ".bak")).unwrap(),
+            "older"
+        );
+    }
+
+    #[test]
+    fn files_that_cant_be_read_are_left_alone() {
+        // reading a folder fails with something other than the file missing
+        let path = temp_dir("versioned-folder").join("config.json");
+        fs::create_dir(&path).unwrap();
+
+        assert!(matches!(
+            read::<File>(&path, &MIGRATIONS),
+            Versioned::Failed(_)
+        ));
+        assert!(path.is_dir());
+        assert!(!with_suffix(&path, ".bak").exists());
+    }
+}
@@ -6,7 +6,6 @@ use iced::{
 };
 use league_client::client;
 use serde_json::Value;
-use tokio::runtime;
 
 #[derive(Debug, Clone)]
 pub(crate) enum Event {
@@ -18,18 +17,13 @@ pub(crate) enum Event {
 
 pub fn connect() -> impl Stream<Item = Event> {
     stream::channel(100, |output| async move {
-        let rt = runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .unwrap();
-
         let output = Arc::new(Mutex::new(output));
 
         loop {
             let output = Arc::clone(&output);
             let sender = output.lock().unwrap().to_owned();
 
-            rt.spawn(async move {
+            tokio::spawn(async move {
                 let _ = lcu(sender).await;
             })
             .await
@@ -40,7 +34,7 @@ pub fn connect() -> impl Stream<Item = Event> {
 
             let _ = sender.send(Event::Disconnected).await;
 
-            rt.spawn(async move {
+            tokio::spawn(async move {
                 let delay = 10;
                 for i in (1..=delay).rev() {
                     let _ = sender.send(Event::Retrying(i)).await;